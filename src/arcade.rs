@@ -1,7 +1,11 @@
+use crate::device::{Device, Flow};
 use crate::machine::{Computer, RunResult};
+use crate::trace::Tracer;
 use terminal_graphics::{Colour, Display};
 use std::collections::VecDeque;
 use std::fmt;
+use std::mem;
+use std::sync::{Arc, Mutex};
 
 pub struct Arcade {
     screen: Vec<Tile>,
@@ -59,9 +63,9 @@ impl Tile {
 
 impl Arcade {
     pub fn new(width: usize, height: usize, cheat: bool, logic_file: &str) -> Arcade {
-        let mut logic = Computer::load(logic_file);
+        let mut logic = Computer::load(logic_file).expect("Couldn't load Intcode program");
 
-        if cheat { logic.write(0, 2); }
+        if cheat { logic.write(0, 2).expect("Couldn't insert quarters"); }
         let mut screen = Vec::with_capacity(width * height);
         screen.resize(width * height, Tile::Empty);
         Arcade {
@@ -75,53 +79,29 @@ impl Arcade {
         }
     }
 
-    pub fn run<F: FnMut(&Arcade)>(mut self, mut redraw: F) -> Self {
-        let mut output_buffer = vec![];
-        let mut input_buffer = VecDeque::new();
-
-        loop {
-            match self.logic.run() {
-                RunResult::Continue(next) =>
-                    self.logic = next,
-                RunResult::Halted(next) => {
-                    self.logic = next;
-                    return self;
-                }
-                RunResult::Output(x, next) => {
-                    self.logic = next;
-                    output_buffer.push(x);
-                    if output_buffer.len() == 3 {
-                        if output_buffer[0] == -1 && output_buffer[1] == 0 {
-                            self.score = output_buffer[2] as usize;
-                        } else {
-                            let x = output_buffer[0] as usize;
-                            let y = output_buffer[1] as usize;
-                            let t = Tile::new(output_buffer[2]);
-                            self.screen[ (y * self.width) + x ] = t;
-                            if t == Tile::Ball {
-                                self.ball = (x, y);
-                                let (paddle_x, _) = self.paddle;
-
-                                if paddle_x < x {
-                                    input_buffer.push_back(Move::Right);
-                                } else if paddle_x > x {
-                                    input_buffer.push_back(Move::Left);
-                                } else {
-                                    input_buffer.push_back(Move::Neutral);
-                                }
-                            }
-                            if t == Tile::HorizontalPaddle {
-                                self.paddle = (x, y);
-                            }
-                        }
-                        output_buffer = vec![];
-                    }
-                }
-                RunResult::Input(c) => {
-                    self.logic = c(input_buffer.pop_front().unwrap().encode());
-                    redraw(&self);
-                }
+    pub fn with_tracer(mut self, tracer: Arc<Mutex<dyn Tracer>>) -> Arcade {
+        self.logic = mem::take(&mut self.logic).with_tracer(tracer);
+        self
+    }
+
+    pub fn run<F: FnMut(&Arcade)>(mut self, redraw: F) -> Self {
+        let logic = mem::take(&mut self.logic);
+        let mut cabinet = Cabinet {
+            arcade: &mut self,
+            redraw,
+            output_buffer: vec![],
+            input_buffer: VecDeque::new(),
+        };
+
+        match logic.run_with(&mut cabinet) {
+            RunResult::Halted(next) => {
+                self.logic = next;
+                self
             }
+            RunResult::Error(e) =>
+                panic!("Arcade program failed: {}", e),
+            _ =>
+                panic!("Arcade program wanted a move before the ball moved"),
         }
     }
 
@@ -151,6 +131,56 @@ impl Arcade {
     }
 }
 
+/// The arcade's joystick and screen, as the game program sees them. The
+/// joystick just follows the ball around.
+struct Cabinet<'a, F> {
+    arcade: &'a mut Arcade,
+    redraw: F,
+    output_buffer: Vec<i64>,
+    input_buffer: VecDeque<Move>,
+}
+
+impl<'a, F: FnMut(&Arcade)> Device for Cabinet<'a, F> {
+    fn next_input(&mut self) -> Option<i64> {
+        let next = self.input_buffer.pop_front().map(|x| x.encode());
+        (self.redraw)(self.arcade);
+        next
+    }
+
+    fn on_output(&mut self, x: i64) -> Flow {
+        self.output_buffer.push(x);
+        if self.output_buffer.len() < 3 {
+            return Flow::Continue;
+        }
+
+        if self.output_buffer[0] == -1 && self.output_buffer[1] == 0 {
+            self.arcade.score = self.output_buffer[2] as usize;
+        } else {
+            let x = self.output_buffer[0] as usize;
+            let y = self.output_buffer[1] as usize;
+            let t = Tile::new(self.output_buffer[2]);
+            self.arcade.screen[ (y * self.arcade.width) + x ] = t;
+            if t == Tile::Ball {
+                self.arcade.ball = (x, y);
+                let (paddle_x, _) = self.arcade.paddle;
+
+                if paddle_x < x {
+                    self.input_buffer.push_back(Move::Right);
+                } else if paddle_x > x {
+                    self.input_buffer.push_back(Move::Left);
+                } else {
+                    self.input_buffer.push_back(Move::Neutral);
+                }
+            }
+            if t == Tile::HorizontalPaddle {
+                self.arcade.paddle = (x, y);
+            }
+        }
+        self.output_buffer = vec![];
+        Flow::Continue
+    }
+}
+
 fn write_to_screen(display: &mut Display, row: isize, s: &str) {
     let mut col = 0;
 
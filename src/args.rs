@@ -1,24 +1,45 @@
 use clap::{App,Arg,SubCommand};
 use crate::arcade::Arcade;
 use crate::image::Image;
-use crate::machine::Computer;
+use crate::isa::InstructionSet;
+use crate::loader::Format;
+use crate::bignum::BigComputer;
+use crate::machine::{Arithmetic, Computer};
 use crate::orbits::UniversalOrbitMap;
+use crate::search::Goal;
+use crate::topology::Network;
+use crate::symbolic::Explorer;
+use crate::transcript::Transcript;
 use crate::wiremap::{Wire};
 use std::fs;
 use std::iter::FromIterator;
 use std::str;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 
 pub enum Command {
     ComputeFuel(Vec<u64>),
-    RunComputer(Computer),
+    RunComputer(Computer, bool, bool, bool, Option<String>),
+    RunBig(BigComputer),
     WireMap(Vec<Wire>),
     Orbits(UniversalOrbitMap),
     PasswordCrack(u32, u32),
-    Amplify(Computer),
+    Amplify(Computer, Goal, usize, usize, usize),
     Image(Image),
-    Arcade(Arcade),
-    FindSanta(Computer),
+    Arcade(Arcade, Option<String>),
+    FindSanta(Computer, Option<String>),
+    Disassemble(Computer, Vec<usize>),
+    ControlFlow(Computer, Vec<usize>, bool),
+    Assemble(String),
+    Debug(Computer),
+    Ascii(Computer, Option<String>),
+    Replay(Computer, Transcript),
+    Benchmark(String, usize),
+    Fuzz(u64, usize),
+    Convert(Computer, String, Format),
+    Symbolic(Explorer, Vec<i64>),
+    Network(Network, Vec<String>, bool),
 }
 
 fn is_number(s: String) -> Result<(), String> {
@@ -28,6 +49,43 @@ fn is_number(s: String) -> Result<(), String> {
     }
 }
 
+fn is_computer(s: String) -> Result<(), String> {
+    match Computer::load(&s) {
+        Err(e) => Err(e.to_string()),
+        Ok(_)  => Ok(()),
+    }
+}
+
+fn is_machine(s: String) -> Result<(), String> {
+    match s.split_once('=') {
+        Some((name, file)) if !name.is_empty() => is_computer(file.to_string()),
+        _                                      => Err(format!("{} should look like NAME=COMPUTER", s)),
+    }
+}
+
+fn is_edge(s: String) -> Result<(), String> {
+    match s.split_once(':') {
+        Some((from, to)) if !from.contains(',') || !to.contains(',') => Ok(()),
+        Some(_) => Err(format!("{} can fan out or fan in, but not both", s)),
+        None    => Err(format!("{} should look like FROM:TO", s)),
+    }
+}
+
+fn is_feed(s: String) -> Result<(), String> {
+    match s.split_once('=') {
+        Some((_, value)) => i64::from_str(value).map(|_| ()).map_err(|e| e.to_string()),
+        None             => Err(format!("{} should look like NAME=VALUE", s)),
+    }
+}
+
+fn is_transcript(s: String) -> Result<(), String> {
+    is_file(s.clone())?;
+    match Transcript::load(&s) {
+        Err(e) => Err(e.to_string()),
+        Ok(_)  => Ok(()),
+    }
+}
+
 fn is_file(s: String) -> Result<(), String> {
     match fs::metadata(&s) {
         Err(e) => Err(e.to_string()),
@@ -44,10 +102,15 @@ impl Command {
                           .about("Runs advent of code programs")
                           .subcommand(SubCommand::with_name("arcade")
                                         .about("Play the arcade game!!")
+                                        .arg(Arg::with_name("RECORD")
+                                                 .short("r")
+                                                 .long("record")
+                                                 .takes_value(true)
+                                                 .help("Save a transcript of the computer's input and output to this file."))
                                         .arg(Arg::with_name("FILE")
                                                  .help("The arcade program")
                                                  .index(1)
-                                                 .validator(is_file))
+                                                 .validator(is_computer))
                                         )
                           .subcommand(SubCommand::with_name("fuel")
                                         .about("runs the fuel computation from day1")
@@ -58,11 +121,61 @@ impl Command {
                                         )
                           .subcommand(SubCommand::with_name("compute")
                                         .about("run the given computer")
+                                        .arg(Arg::with_name("TRACE")
+                                                 .short("t")
+                                                 .long("trace")
+                                                 .help("Print every instruction as it runs."))
+                                        .arg(Arg::with_name("PROFILE")
+                                                 .short("p")
+                                                 .long("profile")
+                                                 .help("Report execution counts once the computer halts."))
+                                        .arg(Arg::with_name("DEBUG_PRINT")
+                                                 .short("d")
+                                                 .long("debug-print")
+                                                 .help("Understand PRINT (opcode 10), which writes its argument to stderr."))
+                                        .arg(Arg::with_name("COVERAGE")
+                                                 .short("c")
+                                                 .long("coverage")
+                                                 .help("Report which instructions ran, and which never did, once the computer halts."))
+                                        .arg(Arg::with_name("RECORD")
+                                                 .short("r")
+                                                 .long("record")
+                                                 .takes_value(true)
+                                                 .help("Save a transcript of the computer's input and output to this file."))
+                                        .arg(Arg::with_name("MEMORY_LIMIT")
+                                                 .short("m")
+                                                 .long("memory-limit")
+                                                 .takes_value(true)
+                                                 .help("The most memory cells the computer may use.")
+                                                 .validator(is_number))
+                                        .arg(Arg::with_name("ARITHMETIC")
+                                                 .short("a")
+                                                 .long("arithmetic")
+                                                 .takes_value(true)
+                                                 .possible_values(&["wrapping", "checked"])
+                                                 .help("What to do when a number gets too big for a cell [default: wrapping]."))
+                                        .arg(Arg::with_name("BIG")
+                                                 .short("b")
+                                                 .long("big")
+                                                 .help("Use cells that never run out. Runs the plain computer only, with none of the other options.")
+                                                 .conflicts_with_all(&["TRACE", "PROFILE", "DEBUG_PRINT", "COVERAGE", "RECORD",
+                                                                       "MEMORY_LIMIT", "ARITHMETIC", "MAX_STEPS", "TIMEOUT"]))
+                                        .arg(Arg::with_name("MAX_STEPS")
+                                                 .short("s")
+                                                 .long("max-steps")
+                                                 .takes_value(true)
+                                                 .help("Stop the computer after this many instructions.")
+                                                 .validator(is_number))
+                                        .arg(Arg::with_name("TIMEOUT")
+                                                 .long("timeout")
+                                                 .takes_value(true)
+                                                 .help("Stop the computer after this many seconds.")
+                                                 .validator(is_number))
                                         .arg(Arg::with_name("COMPUTER")
                                                  .index(1)
                                                  .help("The computer to run.")
                                                  .required(true)
-                                                 .validator(is_file))
+                                                 .validator(is_computer))
                                         )
                            .subcommand(SubCommand::with_name("wiremap")
                                         .about("compute the given wire map")
@@ -95,11 +208,51 @@ impl Command {
                                         )
                            .subcommand(SubCommand::with_name("amplify")
                                         .about("run the given amplifer computer")
+                                        .arg(Arg::with_name("MINIMIZE")
+                                                 .long("minimize")
+                                                 .help("Look for the weakest signal, rather than the strongest."))
+                                        .arg(Arg::with_name("TOP")
+                                                 .short("k")
+                                                 .long("top")
+                                                 .takes_value(true)
+                                                 .help("How many of the best phase settings to show.")
+                                                 .validator(is_number))
+                                        .arg(Arg::with_name("THREADS")
+                                                 .short("j")
+                                                 .long("threads")
+                                                 .takes_value(true)
+                                                 .help("How many threads to search with.")
+                                                 .validator(is_number))
+                                        .arg(Arg::with_name("LENGTH")
+                                                 .short("l")
+                                                 .long("length")
+                                                 .takes_value(true)
+                                                 .help("How many amplifiers to chain, each with a different phase setting.")
+                                                 .validator(|x| match usize::from_str(&x) {
+                                                     Ok(0)  => Err("Need at least one amplifier.".to_string()),
+                                                     Ok(_)  => Ok(()),
+                                                     Err(e) => Err(e.to_string()),
+                                                 }))
                                         .arg(Arg::with_name("COMPUTER")
                                                  .index(1)
                                                  .help("The computer to run.")
                                                  .required(true)
-                                                 .validator(is_file))
+                                                 .validator(is_computer))
+                                        )
+                           .subcommand(SubCommand::with_name("fuzz")
+                                        .about("check the fast interpreter against plain stepping on random programs")
+                                        .arg(Arg::with_name("COUNT")
+                                                 .short("n")
+                                                 .long("count")
+                                                 .help("How many programs to try.")
+                                                 .default_value("10000")
+                                                 .validator(is_number))
+                                        .arg(Arg::with_name("SEED")
+                                                 .short("s")
+                                                 .long("seed")
+                                                 .help("Where to start the random numbers.")
+                                                 .default_value("0")
+                                                 .validator(is_number))
                                         )
                            .subcommand(SubCommand::with_name("image")
                                         .about("run the given image analysis task")
@@ -123,12 +276,188 @@ impl Command {
                                         )
                            .subcommand(SubCommand::with_name("final")
                                         .about("run the final computer")
+                                        .arg(Arg::with_name("CHECKPOINT")
+                                                 .short("c")
+                                                 .long("checkpoint")
+                                                 .takes_value(true)
+                                                 .help("Resume from this snapshot if it exists, or save one there once we reach the checkpoint."))
                                         .arg(Arg::with_name("COMPUTER")
                                                  .index(1)
                                                  .help("The computer to run.")
                                                  .required(true)
+                                                 .validator(is_computer))
+                                        )
+                           .subcommand(SubCommand::with_name("bench")
+                                        .about("time the fast interpreter against plain stepping")
+                                        .arg(Arg::with_name("ROUNDS")
+                                                 .short("n")
+                                                 .long("rounds")
+                                                 .help("How many times to run each workload.")
+                                                 .default_value("3")
+                                                 .validator(is_number))
+                                        .arg(Arg::with_name("INPUTS")
+                                                 .index(1)
+                                                 .help("The directory holding the puzzle inputs.")
+                                                 .default_value("inputs"))
+                                        )
+                           .subcommand(SubCommand::with_name("cfg")
+                                        .about("split the given computer into basic blocks")
+                                        .arg(Arg::with_name("ENTRY")
+                                                 .short("e")
+                                                 .long("entry")
+                                                 .help("An extra address to treat as code.")
+                                                 .multiple(true)
+                                                 .number_of_values(1)
+                                                 .validator(is_number))
+                                        .arg(Arg::with_name("DOT")
+                                                 .short("d")
+                                                 .long("dot")
+                                                 .help("Print a Graphviz graph, rather than a list of blocks."))
+                                        .arg(Arg::with_name("COMPUTER")
+                                                 .index(1)
+                                                 .help("The computer to analyze.")
+                                                 .required(true)
+                                                 .validator(is_computer))
+                                        )
+                           .subcommand(SubCommand::with_name("convert")
+                                        .about("write the given computer out as text or binary")
+                                        .arg(Arg::with_name("BINARY")
+                                                 .short("b")
+                                                 .long("binary")
+                                                 .help("Write a binary image, rather than text."))
+                                        .arg(Arg::with_name("COMPUTER")
+                                                 .index(1)
+                                                 .help("The computer to convert.")
+                                                 .required(true)
+                                                 .validator(is_computer))
+                                        .arg(Arg::with_name("OUTPUT")
+                                                 .index(2)
+                                                 .help("Where to write it.")
+                                                 .required(true))
+                                        )
+                           .subcommand(SubCommand::with_name("disasm")
+                                        .about("disassemble the given computer")
+                                        .arg(Arg::with_name("ENTRY")
+                                                 .short("e")
+                                                 .long("entry")
+                                                 .help("An extra address to treat as code.")
+                                                 .multiple(true)
+                                                 .number_of_values(1)
+                                                 .validator(is_number))
+                                        .arg(Arg::with_name("COMPUTER")
+                                                 .index(1)
+                                                 .help("The computer to disassemble.")
+                                                 .required(true)
+                                                 .validator(is_computer))
+                                        )
+                           .subcommand(SubCommand::with_name("asm")
+                                        .about("assemble the given Intcode assembly file")
+                                        .arg(Arg::with_name("SOURCE")
+                                                 .index(1)
+                                                 .help("The assembly to assemble.")
+                                                 .required(true)
                                                  .validator(is_file))
                                         )
+                           .subcommand(SubCommand::with_name("debug")
+                                        .about("step through the given computer interactively")
+                                        .arg(Arg::with_name("COMPUTER")
+                                                 .index(1)
+                                                 .help("The computer to debug.")
+                                                 .required(true)
+                                                 .validator(is_computer))
+                                        )
+                           .subcommand(SubCommand::with_name("ascii")
+                                        .about("talk to an ASCII-speaking computer over stdin and stdout")
+                                        .arg(Arg::with_name("RECORD")
+                                                 .short("r")
+                                                 .long("record")
+                                                 .takes_value(true)
+                                                 .help("Save a transcript of the computer's input and output to this file."))
+                                        .arg(Arg::with_name("COMPUTER")
+                                                 .index(1)
+                                                 .help("The computer to talk to.")
+                                                 .required(true)
+                                                 .validator(is_computer))
+                                        )
+                           .subcommand(SubCommand::with_name("replay")
+                                        .about("replay a saved transcript, checking the computer does the same thing")
+                                        .arg(Arg::with_name("COMPUTER")
+                                                 .index(1)
+                                                 .help("The computer to run.")
+                                                 .required(true)
+                                                 .validator(is_computer))
+                                        .arg(Arg::with_name("TRANSCRIPT")
+                                                 .index(2)
+                                                 .help("The transcript to check against.")
+                                                 .required(true)
+                                                 .validator(is_transcript))
+                                        )
+                           .subcommand(SubCommand::with_name("network")
+                                        .about("run computers wired together, and show what each one wrote")
+                                        .arg(Arg::with_name("MACHINE")
+                                                 .short("m")
+                                                 .long("machine")
+                                                 .help("A machine, as NAME=COMPUTER.")
+                                                 .required(true)
+                                                 .multiple(true)
+                                                 .number_of_values(1)
+                                                 .validator(is_machine))
+                                        .arg(Arg::with_name("EDGE")
+                                                 .short("e")
+                                                 .long("edge")
+                                                 .help("Send output from one machine to another, as FROM:TO. Either side can list several machines, separated by commas.")
+                                                 .multiple(true)
+                                                 .number_of_values(1)
+                                                 .validator(is_edge))
+                                        .arg(Arg::with_name("INPUT")
+                                                 .short("i")
+                                                 .long("input")
+                                                 .help("A value to give a machine, as NAME=VALUE.")
+                                                 .multiple(true)
+                                                 .number_of_values(1)
+                                                 .allow_hyphen_values(true)
+                                                 .validator(is_feed))
+                                        .arg(Arg::with_name("THREADED")
+                                                 .short("t")
+                                                 .long("threaded")
+                                                 .help("Run each machine on its own thread. Networks with loops in them are refused."))
+                                        )
+                           .subcommand(SubCommand::with_name("symbolic")
+                                        .about("find every path through the given computer, with its inputs left unknown")
+                                        .arg(Arg::with_name("INPUT")
+                                                 .short("i")
+                                                 .long("input")
+                                                 .help("A value to use for the next input, rather than leaving it unknown.")
+                                                 .multiple(true)
+                                                 .number_of_values(1)
+                                                 .allow_hyphen_values(true)
+                                                 .validator(|x| i64::from_str(&x).map(|_| ()).map_err(|e| e.to_string())))
+                                        .arg(Arg::with_name("STEPS")
+                                                 .short("s")
+                                                 .long("max-steps")
+                                                 .takes_value(true)
+                                                 .help("How many steps to follow any one path for.")
+                                                 .validator(is_number))
+                                        .arg(Arg::with_name("PATHS")
+                                                 .short("p")
+                                                 .long("max-paths")
+                                                 .takes_value(true)
+                                                 .help("How many paths to find before giving up.")
+                                                 .validator(is_number))
+                                        .arg(Arg::with_name("AT")
+                                                 .short("a")
+                                                 .long("at")
+                                                 .help("Say which path these inputs take, and what they output on it. Give every input, including the ones given with --input.")
+                                                 .multiple(true)
+                                                 .number_of_values(1)
+                                                 .allow_hyphen_values(true)
+                                                 .validator(|x| i64::from_str(&x).map(|_| ()).map_err(|e| e.to_string())))
+                                        .arg(Arg::with_name("COMPUTER")
+                                                 .index(1)
+                                                 .help("The computer to explore.")
+                                                 .required(true)
+                                                 .validator(is_computer))
+                                        )
                            .get_matches();
 
         if let Some(problem1) = matches.subcommand_matches("fuel") {
@@ -143,8 +472,30 @@ impl Command {
         }
 
         if let Some(problem2) = matches.subcommand_matches("compute") {
-            let computer = Computer::load(problem2.value_of("COMPUTER").unwrap());
-            return Command::RunComputer(computer);
+            let mut computer = Computer::load(problem2.value_of("COMPUTER").unwrap()).unwrap();
+            if problem2.is_present("BIG") {
+                return Command::RunBig(BigComputer::from_computer(&computer));
+            }
+            if problem2.value_of("ARITHMETIC") == Some("checked") {
+                computer = computer.with_arithmetic(Arithmetic::Checked);
+            }
+            if let Some(limit) = problem2.value_of("MEMORY_LIMIT") {
+                computer = computer.with_memory_limit(usize::from_str(limit).unwrap());
+            }
+            if problem2.is_present("DEBUG_PRINT") {
+                computer = computer.with_instruction_set(Arc::new(InstructionSet::debugging()));
+            }
+            if let Some(steps) = problem2.value_of("MAX_STEPS") {
+                computer = computer.with_step_budget(u64::from_str(steps).unwrap());
+            }
+            if let Some(seconds) = problem2.value_of("TIMEOUT") {
+                computer = computer.with_time_limit(Duration::from_secs(u64::from_str(seconds).unwrap()));
+            }
+            let trace = problem2.is_present("TRACE");
+            let profile = problem2.is_present("PROFILE");
+            let coverage = problem2.is_present("COVERAGE");
+            let record = problem2.value_of("RECORD").map(|x| x.to_string());
+            return Command::RunComputer(computer, trace, profile, coverage, record);
         }
 
         if let Some(problem3) = matches.subcommand_matches("wiremap") {
@@ -180,8 +531,12 @@ impl Command {
         }
 
         if let Some(problem6) = matches.subcommand_matches("amplify") {
-            let computer = Computer::load(problem6.value_of("COMPUTER").unwrap());
-            return Command::Amplify(computer);
+            let computer = Computer::load(problem6.value_of("COMPUTER").unwrap()).unwrap();
+            let goal = if problem6.is_present("MINIMIZE") { Goal::Minimize } else { Goal::Maximize };
+            let top = problem6.value_of("TOP").map(|x| usize::from_str(x).unwrap()).unwrap_or(1);
+            let threads = problem6.value_of("THREADS").map(|x| usize::from_str(x).unwrap()).unwrap_or(1);
+            let length = problem6.value_of("LENGTH").map(|x| usize::from_str(x).unwrap()).unwrap_or(5);
+            return Command::Amplify(computer, goal, top, threads, length);
         }
 
         if let Some(problem7) = matches.subcommand_matches("image") {
@@ -194,14 +549,111 @@ impl Command {
 
         if let Some(arcade) = matches.subcommand_matches("arcade") {
             let file = arcade.value_of("FILE").expect("No arcade file!");
+            let record = arcade.value_of("RECORD").map(|x| x.to_string());
             let arcade = Arcade::new(38, 21, true, file);
-            return Command::Arcade(arcade);
+            return Command::Arcade(arcade, record);
         }
 
         if let Some(fin) = matches.subcommand_matches("final") {
             let file = fin.value_of("COMPUTER").expect("No final computer file!");
-            let comp = Computer::load(&file);
-            return Command::FindSanta(comp);
+            let comp = Computer::load(&file).unwrap();
+            let checkpoint = fin.value_of("CHECKPOINT").map(|x| x.to_string());
+            return Command::FindSanta(comp, checkpoint);
+        }
+
+        if let Some(disasm) = matches.subcommand_matches("disasm") {
+            let comp = Computer::load(disasm.value_of("COMPUTER").unwrap()).unwrap();
+            let mut entries = vec![0];
+            if let Some(extra) = disasm.values_of("ENTRY") {
+                entries.extend(extra.map(|x| x.parse::<usize>().unwrap()));
+            }
+            return Command::Disassemble(comp, entries);
+        }
+
+        if let Some(bench) = matches.subcommand_matches("bench") {
+            let rounds = usize::from_str(bench.value_of("ROUNDS").unwrap()).unwrap();
+            return Command::Benchmark(bench.value_of("INPUTS").unwrap().to_string(), rounds);
+        }
+
+        if let Some(fuzz) = matches.subcommand_matches("fuzz") {
+            let count = usize::from_str(fuzz.value_of("COUNT").unwrap()).unwrap();
+            let seed = u64::from_str(fuzz.value_of("SEED").unwrap()).unwrap();
+            return Command::Fuzz(seed, count);
+        }
+
+        if let Some(convert) = matches.subcommand_matches("convert") {
+            let comp = Computer::load(convert.value_of("COMPUTER").unwrap()).unwrap();
+            let format = if convert.is_present("BINARY") { Format::Binary } else { Format::Text };
+            return Command::Convert(comp, convert.value_of("OUTPUT").unwrap().to_string(), format);
+        }
+
+        if let Some(cfg) = matches.subcommand_matches("cfg") {
+            let comp = Computer::load(cfg.value_of("COMPUTER").unwrap()).unwrap();
+            let mut entries = vec![0];
+            if let Some(extra) = cfg.values_of("ENTRY") {
+                entries.extend(extra.map(|x| x.parse::<usize>().unwrap()));
+            }
+            return Command::ControlFlow(comp, entries, cfg.is_present("DOT"));
+        }
+
+        if let Some(asm) = matches.subcommand_matches("asm") {
+            let file_contents = fs::read(asm.value_of("SOURCE").unwrap()).unwrap();
+            let source = str::from_utf8(&file_contents).unwrap();
+            return Command::Assemble(source.to_string());
+        }
+
+        if let Some(debug) = matches.subcommand_matches("debug") {
+            let comp = Computer::load(debug.value_of("COMPUTER").unwrap()).unwrap();
+            return Command::Debug(comp);
+        }
+
+        if let Some(ascii) = matches.subcommand_matches("ascii") {
+            let comp = Computer::load(ascii.value_of("COMPUTER").unwrap()).unwrap();
+            let record = ascii.value_of("RECORD").map(|x| x.to_string());
+            return Command::Ascii(comp, record);
+        }
+
+        if let Some(replay) = matches.subcommand_matches("replay") {
+            let comp = Computer::load(replay.value_of("COMPUTER").unwrap()).unwrap();
+            let transcript = Transcript::load(replay.value_of("TRANSCRIPT").unwrap()).unwrap();
+            return Command::Replay(comp, transcript);
+        }
+
+        if let Some(wiring) = matches.subcommand_matches("network") {
+            let mut network = Network::new();
+            let mut names = vec![];
+            for spec in wiring.values_of("MACHINE").unwrap() {
+                let (name, file) = spec.split_once('=').unwrap();
+                network = network.machine(name, Computer::load(file).unwrap(), &[]);
+                names.push(name.to_string());
+            }
+            for spec in wiring.values_of("EDGE").into_iter().flatten() {
+                let (from, to) = spec.split_once(':').unwrap();
+                let to: Vec<&str> = to.split(',').collect();
+                network = match from.split(',').collect::<Vec<&str>>().as_slice() {
+                    [from] => network.fan_out(from, &to),
+                    from   => network.fan_in(from, to[0]),
+                };
+            }
+            for spec in wiring.values_of("INPUT").into_iter().flatten() {
+                let (name, value) = spec.split_once('=').unwrap();
+                network = network.input(name, i64::from_str(value).unwrap());
+            }
+            return Command::Network(network, names, wiring.is_present("THREADED"));
+        }
+
+        if let Some(symbolic) = matches.subcommand_matches("symbolic") {
+            let comp = Computer::load(symbolic.value_of("COMPUTER").unwrap()).unwrap();
+            let inputs: Vec<i64> = symbolic.values_of("INPUT").map(|x| x.map(|y| i64::from_str(y).unwrap()).collect()).unwrap_or_default();
+            let mut explorer = Explorer::new(&comp).inputs(&inputs);
+            if let Some(steps) = symbolic.value_of("STEPS") {
+                explorer = explorer.max_steps(u64::from_str(steps).unwrap());
+            }
+            if let Some(paths) = symbolic.value_of("PATHS") {
+                explorer = explorer.max_paths(usize::from_str(paths).unwrap());
+            }
+            let at: Vec<i64> = symbolic.values_of("AT").map(|x| x.map(|y| i64::from_str(y).unwrap()).collect()).unwrap_or_default();
+            return Command::Symbolic(explorer, at);
         }
  
         panic!("Failed to run a reasonable command.");
@@ -0,0 +1,169 @@
+use crate::device::{Device, Flow};
+use crate::machine::{Computer, MachineError, RunResult};
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+use std::mem;
+
+/// Where an ASCII program stopped.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AsciiStatus {
+    NeedInput,
+    Halted,
+    /// The machine used up its step budget or its time.
+    OutOfBudget,
+}
+
+/// An Intcode program that talks in ASCII. Input goes in a line at a time,
+/// and output comes back as text; anything the program writes that isn't
+/// ASCII (usually the puzzle answer) is set aside as a value instead.
+#[derive(Clone, Debug)]
+pub struct Ascii {
+    computer: Computer,
+    input: VecDeque<i64>,
+    text: String,
+    values: Vec<i64>,
+}
+
+impl Ascii {
+    pub fn new(computer: Computer) -> Ascii {
+        Ascii {
+            computer,
+            input: VecDeque::new(),
+            text: String::new(),
+            values: vec![],
+        }
+    }
+
+    /// Queue up a line of input; the newline is added for you.
+    pub fn send_line(&mut self, line: &str) {
+        self.input.extend(line.bytes().map(|x| x as i64));
+        self.input.push_back(b'\n' as i64);
+    }
+
+    /// Run until the program halts, or wants input we haven't sent it. If
+    /// the machine fails, it's left as it was before this run.
+    pub fn run(&mut self) -> Result<AsciiStatus, MachineError> {
+        let computer = self.computer.clone();
+
+        match computer.run_with(self) {
+            RunResult::Input(next) => {
+                self.computer = next;
+                Ok(AsciiStatus::NeedInput)
+            }
+            RunResult::Halted(next) => {
+                self.computer = next;
+                Ok(AsciiStatus::Halted)
+            }
+            RunResult::OutOfBudget(next) => {
+                self.computer = next;
+                Ok(AsciiStatus::OutOfBudget)
+            }
+            RunResult::Error(e) =>
+                Err(e),
+            _ =>
+                unreachable!("running only stops for input, a halt, the budget or an error"),
+        }
+    }
+
+    /// All the text the program has written since we last asked.
+    pub fn take_text(&mut self) -> String {
+        mem::take(&mut self.text)
+    }
+
+    /// All the non-ASCII values the program has written since we last asked.
+    pub fn take_values(&mut self) -> Vec<i64> {
+        mem::take(&mut self.values)
+    }
+
+    pub fn into_computer(self) -> Computer {
+        self.computer
+    }
+
+    /// Hook the program up to stdin and stdout, until it halts or we run out
+    /// of input.
+    pub fn interact(mut self) {
+        let stdin = io::stdin();
+        let mut lines = stdin.lock().lines();
+
+        loop {
+            let status = self.run();
+
+            print!("{}", self.take_text());
+            for value in self.take_values() {
+                println!("[value: {}]", value);
+            }
+            let _ = io::stdout().flush();
+
+            match status {
+                Err(e) => {
+                    println!("Machine error: {}", e);
+                    return;
+                }
+                Ok(AsciiStatus::Halted) =>
+                    return,
+                Ok(AsciiStatus::OutOfBudget) => {
+                    println!("Machine ran out of budget.");
+                    return;
+                }
+                Ok(AsciiStatus::NeedInput) => match lines.next() {
+                    Some(Ok(line)) => self.send_line(&line),
+                    _              => return,
+                }
+            }
+        }
+    }
+}
+
+impl Device for Ascii {
+    fn next_input(&mut self) -> Option<i64> {
+        self.input.pop_front()
+    }
+
+    fn on_output(&mut self, value: i64) -> Flow {
+        if (0..128).contains(&value) {
+            self.text.push(value as u8 as char);
+        } else {
+            self.values.push(value);
+        }
+        Flow::Continue
+    }
+}
+
+#[test]
+fn ascii() {
+    // read a line, then say hi and report a (non-ASCII) value
+    let program = "        ADJUST_BASE #buffer
+                     read: INPUT -> rel[0]
+                           EQUALS rel[0], #10 -> [flag]
+                           JMPIF [flag], #shout
+                           ADJUST_BASE #1
+                           JMPIF #1, #read
+                    shout: OUTPUT #72
+                           OUTPUT #73
+                           OUTPUT #10
+                           OUTPUT #1000
+                           HALT
+                     flag: DATA 0
+                   buffer: DATA 0";
+    let computer = Computer::from_string(&crate::assembler::assemble(program).unwrap());
+    let mut ascii = Ascii::new(computer.clone());
+
+    assert_eq!(ascii.run().unwrap(), AsciiStatus::NeedInput);
+    assert_eq!(ascii.take_text(), "");
+    ascii.send_line("hello");
+    assert_eq!(ascii.run().unwrap(), AsciiStatus::Halted);
+    assert_eq!(ascii.take_text(), "HI\n");
+    assert_eq!(ascii.take_values(), vec![1000]);
+    assert_eq!(ascii.take_values(), vec![]);
+    assert!(ascii.into_computer().is_done());
+
+    // a budget stops it, and a failure leaves the machine to look at
+    let mut ascii = Ascii::new(computer.clone().with_step_budget(3));
+    ascii.send_line("hello");
+    assert_eq!(ascii.run().unwrap(), AsciiStatus::OutOfBudget);
+    assert_eq!(ascii.into_computer().steps_left(), Some(0));
+    let broken = Computer::from_string("104,72,1,-1,0,0,99");
+    let mut ascii = Ascii::new(broken.clone());
+    assert!(ascii.run().is_err());
+    assert_eq!(ascii.into_computer(), broken);
+}
@@ -0,0 +1,296 @@
+use crate::machine::{Instruction, Mode};
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, PartialEq)]
+pub struct AsmError {
+    pub line: usize,
+    pub kind: AsmErrorKind,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AsmErrorKind {
+    UnknownMnemonic(String),
+    WrongArgumentCount(&'static str, usize, usize),
+    BadOperand(String),
+    BadValue(String),
+    ImmediateDestination(&'static str),
+    UndefinedLabel(String),
+    DuplicateLabel(String),
+    AddressMismatch(usize, usize),
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: ", self.line)?;
+        match &self.kind {
+            AsmErrorKind::UnknownMnemonic(x) =>
+                write!(f, "unknown mnemonic '{}'", x),
+            AsmErrorKind::WrongArgumentCount(name, expected, found) =>
+                write!(f, "{} takes {} arguments, but got {}", name, expected, found),
+            AsmErrorKind::BadOperand(x) =>
+                write!(f, "couldn't parse argument '{}'", x),
+            AsmErrorKind::BadValue(x) =>
+                write!(f, "couldn't parse value '{}'", x),
+            AsmErrorKind::ImmediateDestination(name) =>
+                write!(f, "{} can't write to an immediate argument", name),
+            AsmErrorKind::UndefinedLabel(x) =>
+                write!(f, "undefined label '{}'", x),
+            AsmErrorKind::DuplicateLabel(x) =>
+                write!(f, "label '{}' defined twice", x),
+            AsmErrorKind::AddressMismatch(expected, found) =>
+                write!(f, "expected to be at address {}, but at {}", expected, found),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+    Number(i64),
+    Label(String, i64),
+}
+
+impl Value {
+    fn parse(s: &str) -> Option<Value> {
+        let s = s.trim();
+
+        if let Ok(x) = s.parse::<i64>() {
+            return Some(Value::Number(x));
+        }
+
+        let (name, offset) = match s.find(['+', '-']) {
+            None => (s, 0),
+            Some(idx) => {
+                let offset = s[idx + 1..].trim().parse::<i64>().ok()?;
+                (s[..idx].trim(), if &s[idx..=idx] == "-" { -offset } else { offset })
+            }
+        };
+
+        if is_label(name) {
+            Some(Value::Label(name.to_string(), offset))
+        } else {
+            None
+        }
+    }
+
+    fn resolve(&self, labels: &HashMap<String, usize>, line: usize) -> Result<i64, AsmError> {
+        match self {
+            Value::Number(x) => Ok(*x),
+            Value::Label(name, offset) => match labels.get(name) {
+                None       => Err(AsmError{ line, kind: AsmErrorKind::UndefinedLabel(name.clone()) }),
+                Some(addr) => Ok(*addr as i64 + offset),
+            },
+        }
+    }
+}
+
+fn is_label(s: &str) -> bool {
+    let mut chars = s.chars();
+
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' =>
+            chars.all(|c| c.is_ascii_alphanumeric() || c == '_'),
+        _ =>
+            false,
+    }
+}
+
+fn parse_operand(s: &str, line: usize) -> Result<(Mode, Value), AsmError> {
+    let s = s.trim();
+    let bad = || AsmError{ line, kind: AsmErrorKind::BadOperand(s.to_string()) };
+
+    let (mode, inner) = if let Some(rest) = s.strip_prefix('#') {
+        (Mode::Immediate, rest)
+    } else if let Some(rest) = s.strip_prefix("rel[").and_then(|x| x.strip_suffix(']')) {
+        (Mode::Relative, rest)
+    } else if let Some(rest) = s.strip_prefix('[').and_then(|x| x.strip_suffix(']')) {
+        (Mode::Position, rest)
+    } else {
+        return Err(bad());
+    };
+
+    Value::parse(inner).map(|v| (mode, v)).ok_or_else(bad)
+}
+
+enum Item {
+    Instruction(Instruction, Vec<Value>),
+    Data(Vec<Value>),
+}
+
+impl Item {
+    fn size(&self) -> usize {
+        match self {
+            Item::Instruction(_, args) => args.len() + 1,
+            Item::Data(values)         => values.len(),
+        }
+    }
+}
+
+/// Assemble the given source, producing program text that
+/// `Computer::from_string` will accept.
+pub fn assemble(source: &str) -> Result<String, AsmError> {
+    let memory = assemble_memory(source)?;
+    let strs: Vec<String> = memory.iter().map(|x| x.to_string()).collect();
+    Ok(strs.join(","))
+}
+
+/// Assemble a small assembly language for Intcode, which uses the same
+/// syntax that the disassembler prints:
+///
+/// ```text
+///         INPUT -> [value]          ; position-mode argument
+///         EQUALS [value], #8 -> [value]
+///         OUTPUT [value]
+///         HALT
+/// value:  DATA -1
+/// ```
+///
+/// `#x` is an immediate argument, `[x]` a position argument, and `rel[x]` a
+/// relative one. Arguments can be numbers, labels, or a label plus or minus
+/// an offset. `DATA` lays down raw values. A label that is just a number
+/// (like the address prefixes in a disassembly) checks that we're at that
+/// address, rather than defining anything.
+pub fn assemble_memory(source: &str) -> Result<Vec<i64>, AsmError> {
+    let mut labels = HashMap::new();
+    let mut items = vec![];
+    let mut address = 0;
+
+    for (idx, raw_line) in source.lines().enumerate() {
+        let line = idx + 1;
+        let mut rest = match raw_line.find(';') {
+            None    => raw_line,
+            Some(x) => &raw_line[..x],
+        }.trim();
+
+        while let Some(colon) = rest.find(':') {
+            let label = rest[..colon].trim();
+
+            if let Ok(expected) = label.parse::<usize>() {
+                if expected != address {
+                    return Err(AsmError{ line, kind: AsmErrorKind::AddressMismatch(expected, address) });
+                }
+            } else if is_label(label) {
+                if labels.insert(label.to_string(), address).is_some() {
+                    return Err(AsmError{ line, kind: AsmErrorKind::DuplicateLabel(label.to_string()) });
+                }
+            } else {
+                break;
+            }
+            rest = rest[colon + 1..].trim();
+        }
+
+        if rest.is_empty() {
+            continue;
+        }
+
+        let (mnemonic, args) = match rest.find(char::is_whitespace) {
+            None    => (rest, ""),
+            Some(x) => (&rest[..x], rest[x..].trim()),
+        };
+        let args = args.trim_start_matches("->");
+        let args: Vec<&str> = if args.trim().is_empty() {
+            vec![]
+        } else {
+            args.split(',').flat_map(|x| x.split("->")).collect()
+        };
+
+        let item = if mnemonic == "DATA" {
+            let mut values = vec![];
+            for arg in args.iter() {
+                let value = Value::parse(arg).ok_or_else(|| AsmError{ line, kind: AsmErrorKind::BadValue(arg.trim().to_string()) })?;
+                values.push(value);
+            }
+            Item::Data(values)
+        } else {
+            let mut instruction = Instruction::from_mnemonic(mnemonic)
+                                      .ok_or_else(|| AsmError{ line, kind: AsmErrorKind::UnknownMnemonic(mnemonic.to_string()) })?;
+            let name = instruction.mnemonic();
+
+            if args.len() != instruction.arity() {
+                return Err(AsmError{ line, kind: AsmErrorKind::WrongArgumentCount(name, instruction.arity(), args.len()) });
+            }
+
+            let mut values = vec![];
+            for (argnum, arg) in args.iter().enumerate() {
+                let (mode, value) = parse_operand(arg, line)?;
+                instruction.modes[argnum] = mode;
+                values.push(value);
+            }
+
+            if instruction.writes() && instruction.modes[instruction.arity() - 1] == Mode::Immediate {
+                return Err(AsmError{ line, kind: AsmErrorKind::ImmediateDestination(name) });
+            }
+
+            Item::Instruction(instruction, values)
+        };
+
+        address += item.size();
+        items.push((line, item));
+    }
+
+    let mut memory = Vec::with_capacity(address);
+
+    for (line, item) in items.iter() {
+        match item {
+            Item::Instruction(instruction, args) => {
+                memory.push(instruction.encode());
+                for arg in args.iter() {
+                    memory.push(arg.resolve(&labels, *line)?);
+                }
+            }
+            Item::Data(values) => {
+                for value in values.iter() {
+                    memory.push(value.resolve(&labels, *line)?);
+                }
+            }
+        }
+    }
+
+    Ok(memory)
+}
+
+#[test]
+fn assembly() {
+    use crate::machine::Computer;
+
+    let equals8 = "        INPUT -> [value]\n\
+                           EQUALS [value], [eight] -> [value]\n\
+                           OUTPUT [value]\n\
+                           HALT\n\
+                   value:  DATA -1\n\
+                   eight:  DATA 8   ; what we compare against\n";
+    assert_eq!(assemble_memory(equals8), Ok(vec![3,9,8,9,10,9,4,9,99,-1,8]));
+    assert_eq!(Computer::from_string(&assemble(equals8).unwrap()).standard_run(&[8]), vec![1]);
+
+    let quine = "start:  ADJUST_BASE #1\n\
+                 loop:   OUTPUT rel[-1]\n\
+                         ADD [count], #1 -> [count]\n\
+                         EQUALS [count], #16 -> [count+1]\n\
+                         JMPNIF [count+1], #start\n\
+                         HALT\n";
+    let mut expected = vec![109,1,204,-1,1001,16,1,16,1008,16,16,17,1006,17,0,99];
+    assert_eq!(Err(AsmError{ line: 3, kind: AsmErrorKind::UndefinedLabel("count".to_string()) }),
+               assemble_memory(quine));
+    let quine = format!("{}count: DATA 0\n", quine);
+    expected.push(0);
+    assert_eq!(assemble_memory(&quine), Ok(expected));
+
+    // a disassembly goes back through unchanged
+    let day9 = Computer::load("inputs/day9").unwrap();
+    let listing = format!("{}", crate::disasm::disassemble(&day9, &[0]));
+    assert_eq!(assemble_memory(&listing).as_ref().map(|x| &x[..]), Ok(day9.memory()));
+
+    let errors = [("FROB [1]", AsmErrorKind::UnknownMnemonic("FROB".to_string())),
+                  ("ADD #1, #2", AsmErrorKind::WrongArgumentCount("ADD", 3, 2)),
+                  ("OUTPUT 4", AsmErrorKind::BadOperand("4".to_string())),
+                  ("INPUT -> #4", AsmErrorKind::ImmediateDestination("INPUT")),
+                  ("DATA 1, x y", AsmErrorKind::BadValue("x y".to_string())),
+                  ("a: HALT\na: HALT", AsmErrorKind::DuplicateLabel("a".to_string())),
+                  ("HALT\n0: HALT", AsmErrorKind::AddressMismatch(0, 1))];
+    for (source, kind) in errors.iter() {
+        let err = assemble_memory(source).unwrap_err();
+        assert_eq!(&err.kind, kind);
+    }
+    assert_eq!("line 2: label 'a' defined twice",
+               format!("{}", assemble_memory("a: HALT\na: HALT").unwrap_err()));
+}
@@ -0,0 +1,94 @@
+use crate::machine::{Computer, MachineError};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// A program to time, and the inputs for each fresh run of it.
+pub struct Workload {
+    pub name: String,
+    computer: Computer,
+    runs: Vec<Vec<i64>>,
+}
+
+impl Workload {
+    pub fn new(name: &str, computer: Computer, runs: Vec<Vec<i64>>) -> Workload {
+        Workload{ name: name.to_string(), computer, runs }
+    }
+
+    fn run_all(&self, run: fn(Computer, &[i64]) -> Vec<i64>) -> (Duration, Vec<Vec<i64>>) {
+        let start = Instant::now();
+        let outputs = self.runs.iter().map(|x| run(self.computer.clone(), x)).collect();
+        (start.elapsed(), outputs)
+    }
+}
+
+/// The brute-force jobs from the puzzles, using the inputs in the given
+/// directory: the BOOST self-test, a scan of the tractor beam that starts a
+/// new machine for every point, and the thermal diagnostics.
+pub fn workloads(dir: &str) -> Result<Vec<Workload>, MachineError> {
+    let boost = Computer::load(&format!("{}/day9", dir))?;
+    let tractor = Computer::load(&format!("{}/day19", dir))?;
+    let diagnostics = Computer::load(&format!("{}/day5", dir))?;
+    let scan = (0..50).flat_map(|x| (0..50).map(move |y| vec![x, y])).collect();
+
+    Ok(vec![Workload::new("day9 BOOST", boost, vec![vec![2]]),
+            Workload::new("day19 50x50 scan", tractor, scan),
+            Workload::new("day5 diagnostics", diagnostics, vec![vec![5]; 100])])
+}
+
+/// How long a workload took on each engine, at best over the rounds run.
+#[derive(Debug)]
+pub struct Timing {
+    pub name: String,
+    pub stepped: Duration,
+    pub fast: Duration,
+}
+
+impl Timing {
+    pub fn speedup(&self) -> f64 {
+        self.stepped.as_secs_f64() / self.fast.as_secs_f64().max(1e-9)
+    }
+}
+
+impl fmt::Display for Timing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:<20} stepped {:>10.3?}   fast {:>10.3?}   {:.2}x",
+               self.name, self.stepped, self.fast, self.speedup())
+    }
+}
+
+/// Run the workload on both engines the given number of times, keeping each
+/// engine's best time. Panics if they don't come up with the same outputs,
+/// since then there's nothing worth comparing.
+pub fn time(workload: &Workload, rounds: usize) -> Timing {
+    let mut timing = Timing{ name: workload.name.clone(), stepped: Duration::MAX, fast: Duration::MAX };
+
+    for _ in 0..rounds.max(1) {
+        let (stepped, expected) = workload.run_all(Computer::stepped_run);
+        let (fast, found) = workload.run_all(Computer::standard_run);
+        assert!(expected == found, "engines disagree on {}", workload.name);
+        timing.stepped = timing.stepped.min(stepped);
+        timing.fast = timing.fast.min(fast);
+    }
+
+    timing
+}
+
+#[test]
+fn benchmarks() {
+    // a loop that rewrites its first instruction from OUTPUT #17 to
+    // OUTPUT [17] and goes round again, so a stale decode would show
+    let rewrites = Computer::from_string("104,17,1008,0,104,19,1006,19,16,1101,0,4,0,1105,1,0,99,42,0,0");
+    assert_eq!(rewrites.clone().stepped_run(&[]), vec![17, 42]);
+    assert_eq!(rewrites.standard_run(&[]), vec![17, 42]);
+
+    let day9 = Computer::load("inputs/day9").unwrap();
+    assert_eq!(day9.clone().stepped_run(&[1]), day9.standard_run(&[1]));
+
+    let workloads = workloads("inputs").unwrap();
+    assert_eq!(workloads.len(), 3);
+    assert_eq!(workloads[1].runs.len(), 2500);
+    let small = Workload::new("day5 diagnostics", Computer::load("inputs/day5").unwrap(), vec![vec![1], vec![5]]);
+    let timing = time(&small, 2);
+    assert!(timing.stepped < Duration::MAX && timing.fast < Duration::MAX);
+    assert!(timing.to_string().starts_with("day5 diagnostics     stepped "));
+}
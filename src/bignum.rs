@@ -0,0 +1,189 @@
+use crate::machine::{Computer, Fault, Instruction, MachineError, Mode};
+use crate::machine::{ADD, ADJUST_BASE, EQUALS, HALT, INPUT, JMPIF, JMPNIF, LESS_THAN, MULTIPLY, OUTPUT};
+use crate::memory::DEFAULT_MEMORY_LIMIT;
+use num_bigint::BigInt;
+use num_traits::{ToPrimitive, Zero};
+use std::collections::HashMap;
+
+/// What a `BigComputer` instruction did.
+enum Outcome {
+    Continue,
+    Output(BigInt),
+    Halted,
+}
+
+/// A computer whose cells hold integers of any size, so that `ADD` and
+/// `MULTIPLY` never overflow. It's a good deal slower than `Computer`, and
+/// doesn't do tracing or budgets, so it's only worth it for programs that
+/// really do need the room.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BigComputer {
+    image: Vec<BigInt>,
+    far: HashMap<usize, BigInt>,
+    position: usize,
+    relative_base: BigInt,
+    limit: usize,
+}
+
+impl BigComputer {
+    /// A copy of the given computer, in the same state, with roomier cells.
+    pub fn from_computer(computer: &Computer) -> BigComputer {
+        let image = computer.memory().iter().map(|x| BigInt::from(*x)).collect();
+        let far = computer.full_memory().sparse_cells().into_iter().map(|(addr, x)| (addr, BigInt::from(x))).collect();
+        BigComputer{ image, far, position: computer.position(), relative_base: BigInt::from(computer.relative_base()),
+                     limit: DEFAULT_MEMORY_LIMIT }
+    }
+
+    pub fn read(&self, idx: usize) -> BigInt {
+        match self.image.get(idx) {
+            Some(x) => x.clone(),
+            None    => self.far.get(&idx).cloned().unwrap_or_else(BigInt::zero),
+        }
+    }
+
+    fn write(&mut self, idx: usize, val: BigInt) -> Result<(), Fault> {
+        if idx < self.image.len() {
+            self.image[idx] = val;
+            return Ok(());
+        }
+        if !self.far.contains_key(&idx) && self.image.len() + self.far.len() >= self.limit {
+            return Err(Fault::OutOfMemory(idx, self.limit));
+        }
+        self.far.insert(idx, val);
+        Ok(())
+    }
+
+    fn address(&self, ptr: BigInt) -> Result<usize, Fault> {
+        if ptr < BigInt::zero() {
+            return Err(Fault::NegativeAddress(ptr.to_i64().unwrap_or(i64::MIN)));
+        }
+        ptr.to_usize().ok_or(Fault::OutOfMemory(usize::MAX, self.limit))
+    }
+
+    fn read_arg(&self, mode: Mode, val: usize) -> Result<BigInt, Fault> {
+        match mode {
+            Mode::Position  => Ok(self.read(self.address(self.read(val))?)),
+            Mode::Immediate => Ok(self.read(val)),
+            Mode::Relative  => Ok(self.read(self.address(self.read(val) + &self.relative_base)?)),
+        }
+    }
+
+    fn read_dest(&self, mode: Mode, val: usize) -> Result<usize, Fault> {
+        match mode {
+            Mode::Position  => self.address(self.read(val)),
+            Mode::Immediate => Err(Fault::ImmediateDestination),
+            Mode::Relative  => self.address(self.read(val) + &self.relative_base),
+        }
+    }
+
+    /// Run the machine on the given inputs until it halts, collecting what
+    /// it writes out. Like `Computer::standard_run`, this panics if the
+    /// inputs run out.
+    pub fn standard_run(mut self, inputs: &[BigInt]) -> Result<Vec<BigInt>, MachineError> {
+        let mut inputs = inputs.iter();
+        let mut res = vec![];
+
+        loop {
+            let address = self.position;
+            // Only the last five digits say anything about the instruction,
+            // so this fits in an i64 whatever's in the cell.
+            let value = (self.read(address) % 100_000i64).to_i64().unwrap();
+            let fault = |fault: Fault| MachineError::Fault(address, value, fault);
+
+            match self.execute(value, &mut inputs).map_err(fault)? {
+                Outcome::Continue  => {}
+                Outcome::Output(x) => res.push(x),
+                Outcome::Halted    => return Ok(res),
+            }
+        }
+    }
+
+    fn execute<'a, I>(&mut self, value: i64, inputs: &mut I) -> Result<Outcome, Fault>
+      where I: Iterator<Item = &'a BigInt>
+    {
+        let instruction = Instruction::decode(value)?;
+        let [arg1mode, arg2mode, arg3mode] = instruction.modes;
+
+        match instruction.opcode {
+            ADD | MULTIPLY | LESS_THAN | EQUALS => {
+                let arg1 = self.read_arg(arg1mode, self.position + 1)?;
+                let arg2 = self.read_arg(arg2mode, self.position + 2)?;
+                let dest = self.read_dest(arg3mode, self.position + 3)?;
+                let result = match instruction.opcode {
+                    ADD       => arg1 + arg2,
+                    MULTIPLY  => arg1 * arg2,
+                    LESS_THAN => BigInt::from((arg1 < arg2) as i64),
+                    _         => BigInt::from((arg1 == arg2) as i64),
+                };
+
+                self.write(dest, result)?;
+                self.position += 4;
+            }
+            INPUT => {
+                let dest = self.read_dest(arg1mode, self.position + 1)?;
+                let input = inputs.next().expect("Ran out of inputs in standard run.");
+
+                self.write(dest, input.clone())?;
+                self.position += 2;
+            }
+            OUTPUT => {
+                let arg1 = self.read_arg(arg1mode, self.position + 1)?;
+                self.position += 2;
+                return Ok(Outcome::Output(arg1));
+            }
+            JMPIF | JMPNIF => {
+                let arg1 = self.read_arg(arg1mode, self.position + 1)?;
+                let arg2 = self.read_arg(arg2mode, self.position + 2)?;
+
+                if (instruction.opcode == JMPIF) != arg1.is_zero() {
+                    self.position = self.address(arg2)?;
+                } else {
+                    self.position += 3;
+                }
+            }
+            ADJUST_BASE => {
+                self.relative_base += self.read_arg(arg1mode, self.position + 1)?;
+                self.position += 2;
+            }
+            HALT =>
+                return Ok(Outcome::Halted),
+            _ =>
+                unreachable!("Instruction::decode accepted opcode {}", instruction.opcode),
+        }
+
+        Ok(Outcome::Continue)
+    }
+}
+
+#[test]
+fn big_numbers() {
+    use crate::machine::{Arithmetic, RunResult};
+    use std::str::FromStr;
+
+    // the day 9 example squared, and then squared again
+    let squares = Computer::from_string("1102,34915192,34915192,13,2,13,13,13,4,13,99,0,0,0");
+    let big = BigComputer::from_computer(&squares).standard_run(&[]).unwrap();
+    assert_eq!(big, vec![BigInt::from_str("1486133206772489918753597034496").unwrap()]);
+    assert_eq!(squares.clone().with_arithmetic(Arithmetic::Wrapping).standard_run(&[]), vec![5149354351239892992]);
+    match squares.with_arithmetic(Arithmetic::Checked).run() {
+        RunResult::Error(MachineError::Fault(4, 2, Fault::Overflow(x, y))) =>
+            assert_eq!((x, y), (1219070632396864, 1219070632396864)),
+        _ => panic!("overflow wasn't caught"),
+    }
+    let added = Computer::from_string("1101,9223372036854775807,1,5,99,0").with_arithmetic(Arithmetic::Checked);
+    assert!(matches!(added.run(), RunResult::Error(MachineError::Fault(0, 1101, Fault::Overflow(_, 1)))));
+
+    // programs that fit in an i64 get the same answers either way
+    let day9 = Computer::load("inputs/day9").unwrap();
+    let expected: Vec<BigInt> = day9.clone().standard_run(&[1]).into_iter().map(BigInt::from).collect();
+    assert_eq!(BigComputer::from_computer(&day9).standard_run(&[BigInt::from(1)]).unwrap(), expected);
+    let quine = Computer::from_string("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99");
+    let expected: Vec<BigInt> = quine.memory().iter().map(|x| BigInt::from(*x)).collect();
+    assert_eq!(BigComputer::from_computer(&quine).standard_run(&[]).unwrap(), expected);
+    let input = BigComputer::from_computer(&Computer::from_string("3,9,8,9,10,9,4,9,99,-1,8"));
+    assert_eq!(input.standard_run(&[BigInt::from(8)]).unwrap(), vec![BigInt::from(1)]);
+
+    // faults still say where they happened
+    let bad = BigComputer::from_computer(&Computer::from_string("1,0,0,0,77"));
+    assert!(matches!(bad.standard_run(&[]), Err(MachineError::Fault(4, 77, Fault::UnknownOpcode(77)))));
+}
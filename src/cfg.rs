@@ -0,0 +1,213 @@
+use crate::disasm::{disassemble, Line, Operand};
+use crate::machine::Computer;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Where control can go when a block ends.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Edge {
+    /// Running on into the instruction after the block.
+    Next(usize),
+    /// A jump to an address given right there in the instruction.
+    Jump(usize),
+    /// A jump to an address read out of memory, which could be anywhere.
+    Indirect,
+}
+
+/// A run of instructions that's only ever entered at the top and left at
+/// the bottom.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Block {
+    pub start: usize,
+    pub lines: Vec<Line>,
+    pub edges: Vec<Edge>,
+}
+
+impl Block {
+    /// The address just past the end of the block.
+    pub fn end(&self) -> usize {
+        self.lines.last().map(|x| x.address() + x.size()).unwrap_or(self.start)
+    }
+}
+
+/// The basic blocks of a program, found from the code `disassemble` turns
+/// up, keyed by their start address.
+pub struct ControlFlow {
+    pub blocks: BTreeMap<usize, Block>,
+}
+
+/// Where control can go after the given instruction. A jump's target is only
+/// known if it's immediate, and a jump on an immediate condition only ever
+/// goes one way.
+fn jump_edges(line: &Line) -> Vec<Edge> {
+    let (address, instruction, operands) = match line {
+        Line::Code{ address, instruction, operands, .. } => (*address, instruction, operands),
+        Line::Data{ .. } => return vec![],
+    };
+    let next = Edge::Next(address + line.size());
+
+    if instruction.is_halt() {
+        return vec![];
+    }
+    if !instruction.is_jump() {
+        return vec![next];
+    }
+
+    let target = match operands[1] {
+        Operand::Immediate(x) if x >= 0 => Edge::Jump(x as usize),
+        _                               => Edge::Indirect,
+    };
+    match operands[0] {
+        Operand::Immediate(x) if instruction.jumps_on(x) => vec![target],
+        Operand::Immediate(_)                             => vec![next],
+        _                                                 => vec![target, next],
+    }
+}
+
+/// Whether control can go anywhere but the next instruction after this.
+fn ends_block(line: &Line) -> bool {
+    jump_edges(line) != vec![Edge::Next(line.address() + line.size())]
+}
+
+/// Split the given computer's code into basic blocks, starting from the
+/// given entry points.
+pub fn control_flow(computer: &Computer, entries: &[usize]) -> ControlFlow {
+    let listing = disassemble(computer, entries);
+    let code: Vec<&Line> = listing.lines().iter().filter(|x| matches!(x, Line::Code{..})).collect();
+
+    // A block starts at an entry point, anywhere something jumps to, and
+    // right after anything that jumps or stops.
+    let mut leaders: BTreeSet<usize> = entries.iter().cloned().collect();
+    for line in code.iter().filter(|x| ends_block(x)) {
+        leaders.insert(line.address() + line.size());
+        for edge in jump_edges(line) {
+            if let Edge::Jump(target) = edge {
+                leaders.insert(target);
+            }
+        }
+    }
+
+    let mut blocks = BTreeMap::new();
+    let mut current: Option<Block> = None;
+
+    for line in code {
+        let address = line.address();
+
+        if current.as_ref().map(|x| x.end() != address || leaders.contains(&address)).unwrap_or(false) {
+            let block = current.take().unwrap();
+            blocks.insert(block.start, block);
+        }
+        let block = current.get_or_insert_with(|| Block{ start: address, lines: vec![], edges: vec![] });
+        block.lines.push(line.clone());
+        block.edges = jump_edges(line);
+        if ends_block(line) {
+            let block = current.take().unwrap();
+            blocks.insert(block.start, block);
+        }
+    }
+    if let Some(block) = current {
+        blocks.insert(block.start, block);
+    }
+
+    ControlFlow{ blocks }
+}
+
+fn edge_name(edge: &Edge) -> String {
+    match edge {
+        Edge::Next(x) => format!("block_{} (next)", x),
+        Edge::Jump(x) => format!("block_{} (jump)", x),
+        Edge::Indirect => "? (indirect)".to_string(),
+    }
+}
+
+impl ControlFlow {
+    /// The graph in Graphviz's DOT language, one box per block.
+    pub fn to_dot(&self) -> String {
+        let mut result = String::from("digraph intcode {\n    node [shape=box, fontname=\"monospace\"];\n");
+
+        for block in self.blocks.values() {
+            let label: String = block.lines.iter().map(|x| format!("{}\\l", x)).collect();
+            result.push_str(&format!("    block_{} [label=\"{}\"];\n", block.start, label));
+            for edge in block.edges.iter() {
+                match edge {
+                    Edge::Next(x) =>
+                        result.push_str(&format!("    block_{} -> block_{} [style=dotted];\n", block.start, x)),
+                    Edge::Jump(x) =>
+                        result.push_str(&format!("    block_{} -> block_{};\n", block.start, x)),
+                    Edge::Indirect => {
+                        result.push_str(&format!("    indirect_{} [label=\"?\", shape=circle];\n", block.start));
+                        result.push_str(&format!("    block_{} -> indirect_{} [style=dashed];\n", block.start, block.start));
+                    }
+                }
+            }
+        }
+
+        result.push_str("}\n");
+        result
+    }
+}
+
+impl fmt::Display for ControlFlow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for block in self.blocks.values() {
+            writeln!(f, "block_{}: {:08}..{:08}", block.start, block.start, block.end())?;
+            for line in block.lines.iter() {
+                writeln!(f, "    {}", line)?;
+            }
+            if block.edges.is_empty() {
+                writeln!(f, "    -> halt")?;
+            }
+            for edge in block.edges.iter() {
+                writeln!(f, "    -> {}", edge_name(edge))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn control_flow_graphs() {
+    let computer = Computer::from_string("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99");
+    let graph = control_flow(&computer, &[0]);
+    assert_eq!(graph.blocks.keys().cloned().collect::<Vec<usize>>(), vec![0, 15]);
+    assert_eq!(graph.blocks[&0].edges, vec![Edge::Jump(0), Edge::Next(15)]);
+    assert_eq!(graph.blocks[&15].edges, vec![]);
+    assert_eq!(graph.to_string(), "block_0: 00000000..00000015\n\
+                                   \x20   00000000: ADJUST_BASE #1\n\
+                                   \x20   00000002: OUTPUT rel[-1]\n\
+                                   \x20   00000004: ADD [100], #1 -> [100]\n\
+                                   \x20   00000008: EQUALS [100], #16 -> [101]\n\
+                                   \x20   00000012: JMPNIF [101], #0\n\
+                                   \x20   -> block_0 (jump)\n\
+                                   \x20   -> block_15 (next)\n\
+                                   block_15: 00000015..00000016\n\
+                                   \x20   00000015: HALT\n\
+                                   \x20   -> halt\n");
+
+    // constant conditions only go one way, and a jump through memory could
+    // go anywhere
+    let computer = Computer::from_string("1106,1,20,1105,1,7,0,1005,12,0,99,0,3");
+    let graph = control_flow(&computer, &[0]);
+    assert_eq!(graph.blocks.keys().cloned().collect::<Vec<usize>>(), vec![0, 7, 10]);
+    assert_eq!(graph.blocks[&0].lines.len(), 2);
+    assert_eq!(graph.blocks[&0].edges, vec![Edge::Jump(7)]);
+    assert_eq!(graph.blocks[&7].edges, vec![Edge::Jump(0), Edge::Next(10)]);
+    let computer = Computer::from_string("5,7,8,99,99");
+    let graph = control_flow(&computer, &[0]);
+    assert_eq!(graph.blocks[&0].edges, vec![Edge::Indirect, Edge::Next(3)]);
+    let dot = graph.to_dot();
+    assert!(dot.starts_with("digraph intcode {\n"));
+    assert!(dot.contains("    block_0 [label=\"00000000: JMPIF [7], [8]\\l\"];\n"));
+    assert!(dot.contains("    block_0 -> indirect_0 [style=dashed];\n"));
+    assert!(dot.contains("    block_0 -> block_3 [style=dotted];\n"));
+
+    // every instruction the disassembler finds ends up in exactly one block
+    let day21 = Computer::load("inputs/day21").unwrap();
+    let graph = control_flow(&day21, &[0]);
+    let listing = disassemble(&day21, &[0]);
+    let code = listing.lines().iter().filter(|x| matches!(x, Line::Code{..})).count();
+    assert_eq!(graph.blocks.values().map(|x| x.lines.len()).sum::<usize>(), code);
+    for block in graph.blocks.values() {
+        assert!(block.lines.windows(2).all(|x| x[0].address() + x[0].size() == x[1].address()));
+    }
+}
@@ -0,0 +1,263 @@
+use crate::disasm::{disassemble, Line};
+use crate::isa::{InstructionSet, Role};
+use crate::machine::{Computer, Instruction, Mode};
+use crate::trace::{Event, Tracer};
+use std::collections::BTreeMap;
+use std::fmt;
+
+const MODES: [Mode; 3] = [Mode::Position, Mode::Immediate, Mode::Relative];
+
+/// The instruction, which has the given number of arguments, with the modes
+/// of any arguments it doesn't have set back to position, so that `1101`
+/// and `11101` count as the same thing.
+fn form_of(instruction: &Instruction, arity: usize) -> Instruction {
+    let mut modes = [Mode::Position; 3];
+    modes[..arity].copy_from_slice(&instruction.modes[..arity]);
+    Instruction{ opcode: instruction.opcode, modes }
+}
+
+/// Every opcode and mode combination a computer with the given instruction
+/// set will actually run: one for each mode of each argument, except that
+/// nothing writes to an immediate.
+pub fn all_forms(set: &InstructionSet) -> Vec<Instruction> {
+    let mut result = vec![];
+
+    for (opcode, operation) in set.operations() {
+        let mut forms = vec![Instruction{ opcode, modes: [Mode::Position; 3] }];
+
+        for (idx, role) in operation.roles.iter().enumerate() {
+            let modes = MODES.iter().filter(|m| !(*role == Role::Write && **m == Mode::Immediate));
+            forms = modes.flat_map(|mode| forms.iter().map(move |form| {
+                let mut form = *form;
+                form.modes[idx] = *mode;
+                form
+            })).collect();
+        }
+        result.extend(forms);
+    }
+
+    result.sort_by_key(|x| x.encode());
+    result
+}
+
+/// Describe an instruction form by its operation's mnemonic and the modes
+/// of its arguments, like "ADD position, immediate -> relative", or "???"
+/// if the instruction set doesn't know it.
+pub fn describe(form: &Instruction, set: &InstructionSet) -> String {
+    let operation = match set.operation(form.opcode) {
+        Some(operation) => operation,
+        None            => return "???".to_string(),
+    };
+    let name = |mode: &Mode| match mode {
+        Mode::Position  => "position",
+        Mode::Immediate => "immediate",
+        Mode::Relative  => "relative",
+    };
+    let mut text = operation.mnemonic.to_string();
+    let mut reads = 0;
+
+    for (mode, role) in form.modes.iter().zip(operation.roles.iter()) {
+        if *role == Role::Write {
+            text.push_str(" -> ");
+        } else {
+            text.push_str(if reads == 0 { " " } else { ", " });
+            reads += 1;
+        }
+        text.push_str(name(mode));
+    }
+    text
+}
+
+/// Keeps track of which instruction forms, and which addresses, a computer
+/// has run. Attach one to several runs of the same program to see how much
+/// of it they exercise between them.
+#[derive(Debug, Default)]
+pub struct Coverage {
+    forms: BTreeMap<i64, u64>,
+    addresses: BTreeMap<usize, u64>,
+}
+
+impl Coverage {
+    pub fn new() -> Coverage {
+        Coverage::default()
+    }
+
+    /// Each instruction form that ran, with how many times it did.
+    pub fn forms(&self) -> Vec<(Instruction, u64)> {
+        self.forms.iter().filter_map(|(code, count)| Some((Instruction::split(*code).ok()?, *count))).collect()
+    }
+
+    /// The forms in `all_forms` for the given program's instruction set that
+    /// never ran.
+    pub fn missing_forms(&self, program: &Computer) -> Vec<Instruction> {
+        all_forms(program.instruction_set()).into_iter().filter(|x| !self.forms.contains_key(&x.encode())).collect()
+    }
+
+    /// How many times the instruction at the given address ran.
+    pub fn count_at(&self, address: usize) -> u64 {
+        self.addresses.get(&address).cloned().unwrap_or(0)
+    }
+
+    /// The given program disassembled, starting from its first cell and
+    /// from everywhere we saw it run.
+    fn lines(&self, program: &Computer) -> Vec<Line> {
+        let entries: Vec<usize> = std::iter::once(0).chain(self.addresses.keys().cloned()).collect();
+        disassemble(program, &entries).lines().to_vec()
+    }
+
+    /// The totals for the given program, which should be the one that was
+    /// run, before it ran.
+    pub fn summary(&self, program: &Computer) -> Summary {
+        let code: Vec<usize> = self.lines(program).iter().filter_map(|line| match line {
+            Line::Code{ address, .. } => Some(*address),
+            Line::Data{ .. }          => None,
+        }).collect();
+
+        Summary{ forms: self.forms.len(),
+                 possible_forms: all_forms(program.instruction_set()).len(),
+                 executed: code.iter().filter(|x| self.addresses.contains_key(x)).count(),
+                 instructions: code.len() }
+    }
+
+    /// The runs of code, as address ranges, that never ran.
+    pub fn unexecuted(&self, program: &Computer) -> Vec<(usize, usize)> {
+        let mut regions: Vec<(usize, usize)> = vec![];
+        let mut extending = false;
+
+        for line in self.lines(program).iter() {
+            let missed = matches!(line, Line::Code{ address, .. } if !self.addresses.contains_key(address));
+            match regions.last_mut() {
+                Some(last) if missed && extending => last.1 = line.address() + line.size(),
+                _ if missed                       => regions.push((line.address(), line.address() + line.size())),
+                _                                 => {}
+            }
+            extending = missed;
+        }
+
+        regions
+    }
+
+    /// A disassembly of the given program, with every instruction marked by
+    /// how often it ran, or with `#####` if it never did, followed by the
+    /// unexecuted regions, the forms that ran, and the ones that didn't.
+    pub fn report(&self, program: &Computer) -> String {
+        let mut text = String::new();
+
+        for line in self.lines(program).iter() {
+            let count = match line {
+                Line::Code{ address, .. } => match self.count_at(*address) {
+                    0 => "#####".to_string(),
+                    x => x.to_string(),
+                },
+                Line::Data{ .. } => String::new(),
+            };
+            text.push_str(&format!("{:>10} | {}\n", count, line));
+        }
+
+        text.push_str(&format!("\n{}\n", self.summary(program)));
+        let regions = self.unexecuted(program);
+        if !regions.is_empty() {
+            let regions: Vec<String> = regions.iter().map(|(start, end)| format!("{}..{}", start, end)).collect();
+            text.push_str(&format!("Unexecuted code: {}\n", regions.join(", ")));
+        }
+        text.push_str("Forms executed:\n");
+        let set = program.instruction_set();
+        for (form, count) in self.forms().iter() {
+            text.push_str(&format!("  {:>5} {:<45} {:>8}\n", form.encode(), describe(form, set), count));
+        }
+        text.push_str("Forms never executed:\n");
+        for form in self.missing_forms(program).iter() {
+            text.push_str(&format!("  {:>5} {}\n", form.encode(), describe(form, set)));
+        }
+        text
+    }
+}
+
+impl Tracer for Coverage {
+    fn trace(&mut self, event: &Event) {
+        *self.forms.entry(form_of(&event.instruction, event.operands.len()).encode()).or_insert(0) += 1;
+        *self.addresses.entry(event.address).or_insert(0) += 1;
+    }
+}
+
+/// How much of a program, and of the instruction set, some runs covered.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Summary {
+    pub forms: usize,
+    pub possible_forms: usize,
+    pub executed: usize,
+    pub instructions: usize,
+}
+
+fn percent(part: usize, whole: usize) -> f64 {
+    if whole == 0 { 100.0 } else { 100.0 * part as f64 / whole as f64 }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Instructions: {} of {} executed ({:.1}%)",
+                 self.executed, self.instructions, percent(self.executed, self.instructions))?;
+        write!(f, "Forms: {} of {} executed ({:.1}%)",
+               self.forms, self.possible_forms, percent(self.forms, self.possible_forms))
+    }
+}
+
+#[test]
+fn coverage() {
+    use crate::machine::ADD;
+    use std::sync::{Arc, Mutex};
+
+    let standard = InstructionSet::standard();
+    assert_eq!(all_forms(standard).len(), 99);
+    assert_eq!(all_forms(standard).iter().filter(|x| x.opcode == ADD).count(), 18);
+    assert_eq!(describe(&Instruction::decode(21001).unwrap(), standard), "ADD position, immediate -> relative");
+    assert_eq!(describe(&Instruction::split(42).unwrap(), standard), "???");
+    assert_eq!(form_of(&Instruction::decode(11104).unwrap(), 1).encode(), 104);
+
+    // the day 5 comparison example only takes one of its three branches on
+    // each input, so it takes all three inputs to cover it
+    let program = Computer::from_string("3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,\
+                                         1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,\
+                                         999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99");
+    let coverage = Arc::new(Mutex::new(Coverage::new()));
+    let computer = program.clone().with_tracer(coverage.clone());
+    assert_eq!(computer.clone().standard_run(&[3]), vec![999]);
+    {
+        let coverage = coverage.lock().unwrap();
+        assert_eq!(coverage.summary(&program), Summary{ forms: 8, possible_forms: 99, executed: 8, instructions: 15 });
+        assert_eq!(coverage.unexecuted(&program), vec![(16, 19), (22, 31), (36, 45)]);
+        assert_eq!(coverage.count_at(31), 1);
+        assert_eq!(coverage.count_at(36), 0);
+        let report = coverage.report(&program);
+        assert!(report.contains("         1 | 00000031: OUTPUT #999\n"));
+        assert!(report.contains("     ##### | 00000036: ADD #1000, #1 -> [20]\n"));
+        assert!(report.contains("           | 00000019: DATA 98, 0, 0\n"));
+        assert!(report.contains("Instructions: 8 of 15 executed (53.3%)\n"));
+        assert!(report.contains("Unexecuted code: 16..19, 22..31, 36..45\n"));
+        assert!(report.contains("   1008 EQUALS position, immediate -> position               1\n"));
+        assert!(report.contains("Forms never executed:\n      1 ADD position, position -> position\n"));
+    }
+    assert_eq!(computer.clone().standard_run(&[8]), vec![1000]);
+    assert_eq!(computer.standard_run(&[192]), vec![1001]);
+    let coverage = coverage.lock().unwrap();
+    assert_eq!(coverage.summary(&program), Summary{ forms: 12, possible_forms: 99, executed: 15, instructions: 15 });
+    assert!(coverage.unexecuted(&program).is_empty());
+    assert_eq!(coverage.count_at(0), 3);
+    assert_eq!(coverage.missing_forms(&program).len(), 87);
+
+    // the day 5 diagnostics run every instruction they can reach, but only
+    // use a handful of the instruction forms
+    let day5 = Computer::load("inputs/day5").unwrap();
+    let coverage = Arc::new(Mutex::new(Coverage::new()));
+    assert_eq!(day5.clone().with_tracer(coverage.clone()).standard_run(&[1]).last(), Some(&7_259_358));
+    let summary = coverage.lock().unwrap().summary(&day5);
+    assert_eq!(summary, Summary{ forms: 12, possible_forms: 99, executed: 61, instructions: 61 });
+
+    // dialect instructions count too, as their own instruction set has them
+    let program = Computer::from_string("104,3,10,0,99").with_instruction_set(Arc::new(InstructionSet::debugging()));
+    let coverage = Arc::new(Mutex::new(Coverage::new()));
+    program.clone().with_tracer(coverage.clone()).standard_run(&[]);
+    let coverage = coverage.lock().unwrap();
+    assert_eq!(coverage.summary(&program), Summary{ forms: 3, possible_forms: 102, executed: 3, instructions: 3 });
+    assert!(coverage.report(&program).contains("     10 PRINT position                                       1\n"));
+}
@@ -0,0 +1,478 @@
+use crate::disasm::decode_at;
+use crate::history::{History, Undone, DEFAULT_HISTORY};
+use crate::isa::InstructionSet;
+use crate::machine::{Computer, Instruction, MachineError, RunResult};
+use crate::transcript::Io;
+use crate::watch::{Access, Watcher};
+use std::collections::{BTreeSet, VecDeque};
+use std::fmt::Write;
+use std::io::{self, BufRead};
+use std::sync::{Arc, Mutex};
+
+/// The most memory cells `mem` will show at once.
+const MAX_CELLS: i64 = 1000;
+
+const HELP: &str = "\
+step [N]          (s)  run N instructions (default 1)
+continue          (c)  run until a breakpoint, a halt, or we need input
+back [N]          (sb) step back N instructions (default 1)
+lastwrite ADDR    (lw) run backwards to the last write to ADDR
+rewind            (rw) run backwards to the last output
+break ADDR|OP     (b)  stop before the given address or opcode (e.g. OUTPUT)
+delete ADDR|OP    (d)  remove a breakpoint
+breakpoints       (bl) list breakpoints
+watch RANGE [r|w] (wa) stop on reads or writes (default both) of RANGE, like 10..20
+unwatch ADDR      (uw) remove any watchpoints covering ADDR
+watches           (wl) list watchpoints
+selfmod           (sm) list writes onto code that had already run
+input V [V ...]   (i)  queue up values for the program to read
+regs              (r)  show position and relative base
+mem ADDR [N]      (x)  show N (at most 1000) memory cells from ADDR, marking ones that ran as code
+set ADDR VALUE    (w)  write VALUE to memory at ADDR
+list [ADDR] [N]   (l)  disassemble N instructions from ADDR (default position)
+help              (h)  show this message
+quit              (q)  leave the debugger";
+
+enum Break {
+    Address(usize),
+    Opcode(i64),
+}
+
+/// Why a run of the machine stopped.
+enum Stop {
+    Stepped,
+    Breakpoint,
+    Watchpoint,
+    NeedInput,
+    Halted,
+    OutOfBudget,
+    Failed(MachineError),
+}
+
+/// An interactive debugger wrapped around `Computer::step`. A machine that
+/// stops at an `INPUT` stays there until the user queues up a value for it,
+/// so there's a chance to look around first. Every step goes into a
+/// `History`, so we can also go backwards.
+pub struct Debugger {
+    computer: Option<Computer>,
+    history: History,
+    watcher: Arc<Mutex<Watcher>>,
+    address_breaks: BTreeSet<usize>,
+    opcode_breaks: BTreeSet<i64>,
+    inputs: VecDeque<i64>,
+    outputs: Vec<i64>,
+}
+
+impl Debugger {
+    pub fn new(computer: Computer) -> Debugger {
+        let watcher = Arc::new(Mutex::new(Watcher::new()));
+        Debugger {
+            computer: Some(computer.with_tracer(watcher.clone())),
+            history: History::new(DEFAULT_HISTORY),
+            watcher,
+            address_breaks: BTreeSet::new(),
+            opcode_breaks: BTreeSet::new(),
+            inputs: VecDeque::new(),
+            outputs: vec![],
+        }
+    }
+
+    fn computer(&mut self) -> &mut Computer {
+        self.computer.as_mut().expect("debugger lost its computer")
+    }
+
+    fn next_instruction(&mut self) -> Option<Instruction> {
+        let computer = self.computer();
+        let position = computer.position();
+        computer.instruction_set().decode(computer.read(position)).ok()
+    }
+
+    fn at_breakpoint(&mut self) -> bool {
+        let position = self.computer().position();
+        self.address_breaks.contains(&position) ||
+            self.next_instruction().map(|i| self.opcode_breaks.contains(&i.opcode)).unwrap_or(false)
+    }
+
+    /// Execute a single instruction, if we can.
+    fn step_once(&mut self, out: &mut String) -> Option<Stop> {
+        if self.computer().is_done() {
+            return Some(Stop::Halted);
+        }
+
+        // A faulting instruction takes the machine with it, so hang on to a
+        // copy to look at afterwards.
+        let computer = self.computer.take().expect("debugger lost its computer");
+        self.computer = Some(computer.clone());
+        match self.history.step(computer) {
+            RunResult::Continue(next) =>
+                self.computer = Some(next),
+            RunResult::Halted(next) => {
+                self.computer = Some(next);
+                return Some(Stop::Halted);
+            }
+            RunResult::Output(value, next) => {
+                let _ = writeln!(out, "OUTPUT: {}", value);
+                self.outputs.push(value);
+                self.computer = Some(next);
+            }
+            RunResult::Input(next) => match self.inputs.pop_front() {
+                None => {
+                    self.computer = Some(next);
+                    return Some(Stop::NeedInput);
+                }
+                Some(value) => {
+                    let _ = writeln!(out, "INPUT: {}", value);
+                    self.computer = Some(self.history.provide_input(next, value));
+                }
+            }
+            RunResult::Error(e) =>
+                return Some(Stop::Failed(e)),
+            RunResult::OutOfBudget(next) => {
+                self.computer = Some(next);
+                return Some(Stop::OutOfBudget);
+            }
+        }
+
+        None
+    }
+
+    fn run(&mut self, steps: Option<usize>, out: &mut String) -> Stop {
+        let mut taken = 0;
+
+        loop {
+            if let Some(stop) = self.step_once(out) {
+                return stop;
+            }
+            taken += 1;
+            let hits = self.watcher.lock().unwrap().take_hits();
+            for hit in hits.iter() {
+                let _ = writeln!(out, "WATCH: {}", hit);
+            }
+            if !hits.is_empty() {
+                return Stop::Watchpoint;
+            }
+            if steps.map(|x| taken >= x).unwrap_or(false) {
+                return Stop::Stepped;
+            }
+            if self.at_breakpoint() {
+                return Stop::Breakpoint;
+            }
+        }
+    }
+
+    /// Undo a single step, putting back any input it read and forgetting
+    /// any output it wrote.
+    fn step_back(&mut self) -> Option<Undone> {
+        let computer = self.computer.as_mut().expect("debugger lost its computer");
+        let undone = self.history.undo(computer)?;
+
+        match undone.io {
+            Some(Io::Input(value)) => self.inputs.push_front(value),
+            Some(Io::Output(_))    => { self.outputs.pop(); }
+            None                   => {}
+        }
+        Some(undone)
+    }
+
+    /// Go backwards until we've undone a step the given test likes, or up to
+    /// `limit` steps, whichever comes first.
+    fn run_back<F: Fn(&Undone) -> bool>(&mut self, limit: Option<usize>, found: F, out: &mut String) {
+        let mut taken = 0;
+
+        while limit.map(|x| taken < x).unwrap_or(true) {
+            match self.step_back() {
+                None => {
+                    let _ = writeln!(out, "No more history.");
+                    break;
+                }
+                Some(undone) if found(&undone) => break,
+                Some(_) => taken += 1,
+            }
+        }
+        self.show_position(out);
+    }
+
+    fn show_position(&mut self, out: &mut String) {
+        let computer = self.computer();
+        let line = decode_at(computer.instruction_set(), computer.memory(), computer.position());
+        let _ = writeln!(out, "{}    [relative base {}]", line, computer.relative_base());
+    }
+
+    fn report(&mut self, stop: Stop, out: &mut String) {
+        match stop {
+            Stop::Stepped     => {}
+            Stop::Breakpoint  => { let _ = writeln!(out, "Breakpoint."); }
+            Stop::Watchpoint  => { let _ = writeln!(out, "Watchpoint."); }
+            Stop::NeedInput   => { let _ = writeln!(out, "Waiting for input; queue some with 'input'."); }
+            Stop::Halted      => { let _ = writeln!(out, "Machine halted."); return; }
+            Stop::OutOfBudget => { let _ = writeln!(out, "Machine ran out of budget."); }
+            Stop::Failed(e)   => { let _ = writeln!(out, "Machine error: {}", e); }
+        }
+        self.show_position(out);
+    }
+
+    fn parse_breakpoint(set: &InstructionSet, arg: Option<&str>) -> Result<Break, String> {
+        match arg {
+            None => Err("Need an address or an opcode name.".to_string()),
+            Some(x) => match x.parse::<usize>() {
+                Ok(addr) => Ok(Break::Address(addr)),
+                Err(_)   => match set.operations().find(|(_, op)| op.mnemonic == x.to_uppercase()) {
+                    Some((opcode, _)) => Ok(Break::Opcode(opcode)),
+                    None              => Err(format!("'{}' is neither an address nor an opcode.", x)),
+                }
+            }
+        }
+    }
+
+    fn parse_watchpoint(args: &[&str]) -> Option<(usize, usize, Access)> {
+        let (start, end) = match args.first()?.split_once("..") {
+            None             => (args[0], args[0]),
+            Some((from, to)) => (from, to),
+        };
+        let access = match args.get(1).cloned() {
+            None | Some("rw") => Access::Any,
+            Some("r")         => Access::Read,
+            Some("w")         => Access::Write,
+            Some(_)           => return None,
+        };
+        Some((start.parse().ok()?, end.parse().ok()?, access))
+    }
+
+    /// Run a single debugger command, returning what it printed, or `None`
+    /// if the user asked to quit.
+    pub fn execute(&mut self, command: &str) -> Option<String> {
+        let mut out = String::new();
+        let mut words = command.split_whitespace();
+        let verb = match words.next() {
+            None    => return Some(out),
+            Some(x) => x,
+        };
+        let args: Vec<&str> = words.collect();
+        let number = |idx: usize| args.get(idx).and_then(|x| x.parse::<i64>().ok());
+
+        match verb {
+            "step" | "s" => {
+                let count = number(0).unwrap_or(1).max(1) as usize;
+                let stop = self.run(Some(count), &mut out);
+                self.report(stop, &mut out);
+            }
+            "continue" | "c" => {
+                let stop = self.run(None, &mut out);
+                self.report(stop, &mut out);
+            }
+            "back" | "sb" => {
+                let count = number(0).unwrap_or(1).max(1) as usize;
+                self.run_back(Some(count), |_| false, &mut out);
+            }
+            "lastwrite" | "lw" => match number(0) {
+                Some(addr) if addr >= 0 =>
+                    self.run_back(None, |x| x.write == Some(addr as usize), &mut out),
+                _ => { let _ = writeln!(out, "Need an address."); }
+            }
+            "rewind" | "rw" =>
+                self.run_back(None, |x| matches!(x.io, Some(Io::Output(_))), &mut out),
+            "break" | "b" => match Debugger::parse_breakpoint(self.computer().instruction_set(), args.first().cloned()) {
+                Err(e)                    => { let _ = writeln!(out, "{}", e); }
+                Ok(Break::Address(addr))  => { self.address_breaks.insert(addr); }
+                Ok(Break::Opcode(op))     => { self.opcode_breaks.insert(op); }
+            }
+            "delete" | "d" => match Debugger::parse_breakpoint(self.computer().instruction_set(), args.first().cloned()) {
+                Err(e)                    => { let _ = writeln!(out, "{}", e); }
+                Ok(Break::Address(addr))  => { self.address_breaks.remove(&addr); }
+                Ok(Break::Opcode(op))     => { self.opcode_breaks.remove(&op); }
+            }
+            "breakpoints" | "bl" => {
+                for addr in self.address_breaks.iter() {
+                    let _ = writeln!(out, "address {}", addr);
+                }
+                let set = self.computer.as_ref().expect("debugger lost its computer").instruction_set();
+                for op in self.opcode_breaks.iter() {
+                    let name = set.operation(*op).map(|x| x.mnemonic).unwrap_or("???");
+                    let _ = writeln!(out, "opcode {}", name);
+                }
+            }
+            "watch" | "wa" => match Debugger::parse_watchpoint(&args) {
+                Some((start, end, access)) if start <= end =>
+                    self.watcher.lock().unwrap().watch(start..=end, access),
+                _ => { let _ = writeln!(out, "Need an address or range, like 10 or 10..20, then r, w, or rw."); }
+            }
+            "unwatch" | "uw" => match number(0) {
+                Some(addr) if addr >= 0 => self.watcher.lock().unwrap().unwatch(addr as usize),
+                _ => { let _ = writeln!(out, "Need an address."); }
+            }
+            "watches" | "wl" => {
+                for watch in self.watcher.lock().unwrap().watchpoints() {
+                    let _ = writeln!(out, "{}", watch);
+                }
+            }
+            "selfmod" | "sm" => {
+                for modification in self.watcher.lock().unwrap().modifications() {
+                    let _ = writeln!(out, "{}", modification);
+                }
+            }
+            "input" | "i" => {
+                for arg in args.iter() {
+                    match arg.parse::<i64>() {
+                        Ok(x)  => self.inputs.push_back(x),
+                        Err(_) => { let _ = writeln!(out, "Bad input value '{}'", arg); }
+                    }
+                }
+                let _ = writeln!(out, "Queued inputs: {:?}", self.inputs);
+            }
+            "regs" | "r" => {
+                let computer = self.computer();
+                let _ = writeln!(out, "position: {}", computer.position());
+                let _ = writeln!(out, "relative base: {}", computer.relative_base());
+                let _ = writeln!(out, "halted: {}", computer.is_done());
+                let _ = writeln!(out, "steps to go back: {}", self.history.steps());
+                let _ = writeln!(out, "outputs so far: {:?}", self.outputs);
+            }
+            "mem" | "x" => match number(0) {
+                Some(start) if start >= 0 => {
+                    let count = number(1).unwrap_or(1).clamp(1, MAX_CELLS) as usize;
+                    for addr in (start as usize)..(start as usize).saturating_add(count) {
+                        let value = self.computer().read(addr);
+                        let code = if self.watcher.lock().unwrap().is_code(addr) { "    (code)" } else { "" };
+                        let _ = writeln!(out, "{:08}: {}{}", addr, value, code);
+                    }
+                }
+                _ => { let _ = writeln!(out, "Need an address."); }
+            }
+            "set" | "w" => match (number(0), number(1)) {
+                (Some(addr), Some(value)) if addr >= 0 => {
+                    let computer = self.computer.as_mut().expect("debugger lost its computer");
+                    if let Err(e) = self.history.write(computer, addr as usize, value) {
+                        let _ = writeln!(out, "{}", e);
+                    }
+                }
+                _ => { let _ = writeln!(out, "Need an address and a value."); }
+            }
+            "list" | "l" => {
+                let mut addr = number(0).map(|x| x.max(0) as usize).unwrap_or_else(|| self.computer().position());
+                let count = number(1).unwrap_or(5).max(1) as usize;
+                let position = self.computer().position();
+                for _ in 0..count {
+                    if addr >= self.computer().memory().len() {
+                        break;
+                    }
+                    let computer = self.computer();
+                    let line = decode_at(computer.instruction_set(), computer.memory(), addr);
+                    let marker = if addr == position { "=>" } else { "  " };
+                    let _ = writeln!(out, "{} {}", marker, line);
+                    addr += line.size();
+                }
+            }
+            "help" | "h" => {
+                let _ = writeln!(out, "{}", HELP);
+            }
+            "quit" | "q" =>
+                return None,
+            _ => {
+                let _ = writeln!(out, "Unknown command '{}'; try 'help'.", verb);
+            }
+        }
+
+        Some(out)
+    }
+
+    pub fn interact(mut self) {
+        let stdin = io::stdin();
+        let mut out = String::new();
+
+        self.show_position(&mut out);
+        print!("{}", out);
+        println!("Type 'help' for a list of commands.");
+
+        for line in stdin.lock().lines() {
+            let line = match line {
+                Err(_) => break,
+                Ok(x)  => x,
+            };
+
+            match self.execute(&line) {
+                None         => break,
+                Some(output) => print!("{}", output),
+            }
+        }
+    }
+}
+
+#[test]
+fn debugging() {
+    let mut debugger = Debugger::new(Computer::from_string("3,9,8,9,10,9,4,9,99,-1,8"));
+
+    assert_eq!(debugger.execute("step"), Some("Waiting for input; queue some with 'input'.\n\
+                                               00000000: INPUT -> [9]    [relative base 0]\n".to_string()));
+    assert_eq!(debugger.execute("i 8"), Some("Queued inputs: [8]\n".to_string()));
+    assert_eq!(debugger.execute("b OUTPUT"), Some("".to_string()));
+    assert_eq!(debugger.execute("c"), Some("INPUT: 8\n\
+                                            Breakpoint.\n\
+                                            00000006: OUTPUT [9]    [relative base 0]\n".to_string()));
+    assert_eq!(debugger.execute("x 9 2"), Some("00000009: 1\n00000010: 8\n".to_string()));
+    assert_eq!(debugger.execute("x 1"), Some("00000001: 9    (code)\n".to_string()));
+    assert_eq!(debugger.execute("x 0 9223372036854775807").unwrap().lines().count(), 1000);
+    assert_eq!(debugger.execute("x 9223372036854775807 2").unwrap().lines().count(), 2);
+    assert_eq!(debugger.execute("w 9 5"), Some("".to_string()));
+    assert_eq!(debugger.execute("c"), Some("OUTPUT: 5\nMachine halted.\n".to_string()));
+    assert_eq!(debugger.execute("r"), Some("position: 8\nrelative base: 0\nhalted: true\nsteps to go back: 5\noutputs so far: [5]\n".to_string()));
+    assert_eq!(debugger.execute("q"), None);
+
+    let mut debugger = Debugger::new(Computer::from_string("1101,-3,-2,5,4,0,99"));
+    assert_eq!(debugger.execute("c"), Some("Machine error: Negative address -5 at address 4 (instruction 4)\n\
+                                            00000004: OUTPUT [-5]    [relative base 0]\n".to_string()));
+
+    // a machine that came with a budget stops when it's used up
+    let mut debugger = Debugger::new(Computer::from_string("1105,1,0").with_step_budget(3));
+    assert_eq!(debugger.execute("c"), Some("Machine ran out of budget.\n\
+                                            00000000: JMPIF #1, #0    [relative base 0]\n".to_string()));
+
+    // a dialect's instructions can be listed, stepped and broken on
+    let dialect = Computer::from_string("104,3,10,0,99").with_instruction_set(std::sync::Arc::new(InstructionSet::debugging()));
+    let mut debugger = Debugger::new(dialect);
+    assert_eq!(debugger.execute("b print"), Some("".to_string()));
+    assert_eq!(debugger.execute("bl"), Some("opcode PRINT\n".to_string()));
+    assert_eq!(debugger.execute("c"), Some("OUTPUT: 3\n\
+                                            Breakpoint.\n\
+                                            00000002: PRINT [0]    [relative base 0]\n".to_string()));
+    assert_eq!(debugger.execute("s"), Some("00000004: HALT    [relative base 0]\n".to_string()));
+
+    // watchpoints stop us just after the access
+    let mut debugger = Debugger::new(Computer::from_string("1101,1,1,1,1106,0,8,0,1,0,0,0,99"));
+    assert_eq!(debugger.execute("watch 0..1 w"), Some("".to_string()));
+    assert_eq!(debugger.execute("watch 12 r"), Some("".to_string()));
+    assert_eq!(debugger.execute("wa x"), Some("Need an address or range, like 10 or 10..20, then r, w, or rw.\n".to_string()));
+    assert_eq!(debugger.execute("wl"), Some("writes to 0..1\nreads of 12..12\n".to_string()));
+    assert_eq!(debugger.execute("c"), Some("WATCH: 00000000 wrote 2 to 1\n\
+                                            Watchpoint.\n\
+                                            00000004: JMPNIF #0, #8    [relative base 0]\n".to_string()));
+    assert_eq!(debugger.execute("c"), Some("WATCH: 00000008 wrote 2202 to 0\n\
+                                            Watchpoint.\n\
+                                            00000012: HALT    [relative base 0]\n".to_string()));
+    assert_eq!(debugger.execute("sm"), Some("00000000 rewrote code at 1 to 2\n\
+                                             00000008 rewrote code at 0 to 2202\n".to_string()));
+    assert_eq!(debugger.execute("uw 0"), Some("".to_string()));
+    assert_eq!(debugger.execute("wl"), Some("reads of 12..12\n".to_string()));
+
+    // going backwards puts inputs back in the queue, and takes outputs away
+    let mut debugger = Debugger::new(Computer::from_string("3,9,8,9,10,9,4,9,99,-1,8"));
+    debugger.execute("i 8");
+    assert_eq!(debugger.execute("c"), Some("INPUT: 8\nOUTPUT: 1\nMachine halted.\n".to_string()));
+    assert_eq!(debugger.execute("rw"), Some("00000006: OUTPUT [9]    [relative base 0]\n".to_string()));
+    assert!(debugger.execute("r").unwrap().ends_with("outputs so far: []\n"));
+    assert_eq!(debugger.execute("lw 9"), Some("00000002: EQUALS [9], [10] -> [9]    [relative base 0]\n".to_string()));
+    assert_eq!(debugger.execute("x 9"), Some("00000009: 8\n".to_string()));
+    assert_eq!(debugger.execute("back 5"), Some("No more history.\n\
+                                                 00000000: INPUT -> [9]    [relative base 0]\n".to_string()));
+    assert_eq!(debugger.execute("x 9"), Some("00000009: -1\n".to_string()));
+    assert_eq!(debugger.execute("c"), Some("INPUT: 8\nOUTPUT: 1\nMachine halted.\n".to_string()));
+    assert_eq!(debugger.execute("w 9 42"), Some("".to_string()));
+    assert_eq!(debugger.execute("lw 9"), Some("00000008: HALT    [relative base 0]\n".to_string()));
+    assert_eq!(debugger.execute("x 9"), Some("00000009: 1\n".to_string()));
+
+    let mut debugger = Debugger::new(Computer::load("inputs/day9").unwrap());
+    debugger.execute("break output");
+    debugger.execute("input 1");
+    assert!(debugger.execute("continue").unwrap().contains("Breakpoint.\n"));
+    assert!(debugger.execute("step").unwrap().starts_with("OUTPUT: 3063082071\n"));
+    assert_eq!(debugger.execute("continue"), Some("Machine halted.\n".to_string()));
+}
@@ -0,0 +1,43 @@
+/// Whether a device wants the machine it's attached to to keep going.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Flow {
+    Continue,
+    Stop,
+}
+
+/// Something attached to an Intcode machine's input and output: a screen, a
+/// droid, a network card. `Computer::run_with` does all the stepping, and
+/// just calls out to the device whenever the machine reads or writes.
+pub trait Device {
+    /// The machine wants a value. Returning `None` stops the run, leaving
+    /// the machine waiting for input.
+    fn next_input(&mut self) -> Option<i64>;
+
+    /// The machine wrote a value. Returning `Flow::Stop` stops the run right
+    /// after it.
+    fn on_output(&mut self, value: i64) -> Flow;
+}
+
+/// Hands a machine values from a list, and keeps everything it writes. Runs
+/// out, leaving the machine waiting, when the list does.
+pub struct Feeder<'a> {
+    inputs: std::slice::Iter<'a, i64>,
+    pub outputs: Vec<i64>,
+}
+
+impl<'a> Feeder<'a> {
+    pub fn new(inputs: &'a [i64]) -> Feeder<'a> {
+        Feeder{ inputs: inputs.iter(), outputs: vec![] }
+    }
+}
+
+impl Device for Feeder<'_> {
+    fn next_input(&mut self) -> Option<i64> {
+        self.inputs.next().cloned()
+    }
+
+    fn on_output(&mut self, value: i64) -> Flow {
+        self.outputs.push(value);
+        Flow::Continue
+    }
+}
@@ -0,0 +1,316 @@
+use crate::isa::{InstructionSet, Operation, Role};
+use crate::machine::{Computer, Instruction, Mode};
+use std::collections::BTreeSet;
+use std::fmt;
+
+const DATA_PER_LINE: usize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operand {
+    Position(i64),
+    Immediate(i64),
+    Relative(i64),
+}
+
+impl Operand {
+    pub fn new(mode: Mode, value: i64) -> Operand {
+        match mode {
+            Mode::Position  => Operand::Position(value),
+            Mode::Immediate => Operand::Immediate(value),
+            Mode::Relative  => Operand::Relative(value),
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Position(x)  => write!(f, "[{}]", x),
+            Operand::Immediate(x) => write!(f, "#{}", x),
+            Operand::Relative(x)  => write!(f, "rel[{}]", x),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Line {
+    /// An instruction, named and with its arguments laid out by the
+    /// instruction set it was decoded with.
+    Code {
+        address: usize,
+        instruction: Instruction,
+        mnemonic: &'static str,
+        roles: &'static [Role],
+        operands: Vec<Operand>,
+    },
+    Data {
+        address: usize,
+        values: Vec<i64>,
+    },
+}
+
+impl Line {
+    pub fn address(&self) -> usize {
+        match self {
+            Line::Code{ address, .. } => *address,
+            Line::Data{ address, .. } => *address,
+        }
+    }
+
+    /// The number of memory cells this line covers.
+    pub fn size(&self) -> usize {
+        match self {
+            Line::Code{ operands, .. } => operands.len() + 1,
+            Line::Data{ values, .. }   => values.len(),
+        }
+    }
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Line::Code{ address, mnemonic, roles, operands, .. } => {
+                write!(f, "{:08}: {}", address, mnemonic)?;
+                let with_role = |role| operands.iter().zip(roles.iter()).filter(move |(_, x)| **x == role).map(|(x, _)| x);
+                for (idx, operand) in with_role(Role::Read).enumerate() {
+                    write!(f, "{}{}", if idx == 0 { " " } else { ", " }, operand)?;
+                }
+                for operand in with_role(Role::Write) {
+                    write!(f, " -> {}", operand)?;
+                }
+                Ok(())
+            }
+            Line::Data{ address, values } => {
+                write!(f, "{:08}: DATA ", address)?;
+                for (idx, value) in values.iter().enumerate() {
+                    write!(f, "{}{}", if idx == 0 { "" } else { ", " }, value)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Claim {
+    Unknown,
+    Start,
+    Argument,
+}
+
+/// A disassembled program. Code is found by following control flow from a
+/// set of entry points; anything that isn't reached that way is treated as
+/// data.
+pub struct Listing {
+    lines: Vec<Line>,
+    jump_targets: BTreeSet<usize>,
+}
+
+impl Listing {
+    pub fn lines(&self) -> &[Line] {
+        &self.lines
+    }
+}
+
+impl fmt::Display for Listing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut in_data = false;
+
+        for line in self.lines.iter() {
+            match line {
+                Line::Data{ address, .. } if !in_data => {
+                    writeln!(f, "data_{}:", address)?;
+                    in_data = true;
+                }
+                Line::Data{ .. } => {}
+                Line::Code{ address, .. } => {
+                    if self.jump_targets.contains(address) {
+                        writeln!(f, "label_{}:", address)?;
+                    }
+                    in_data = false;
+                }
+            }
+            writeln!(f, "{}", line)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Disassemble the given computer's memory with its instruction set,
+/// treating each of the given addresses as the start of some code.
+pub fn disassemble(computer: &Computer, entries: &[usize]) -> Listing {
+    let (set, memory) = (computer.instruction_set(), computer.memory());
+    let mut claims = vec![Claim::Unknown; memory.len()];
+    let mut jump_targets = BTreeSet::new();
+    let mut stored_constants = vec![];
+
+    trace_from(set, memory, entries, &mut claims, &mut jump_targets, &mut stored_constants);
+    // Calls in Intcode are made by storing the return address somewhere and
+    // jumping, so the address only ever shows up as a constant. So once we've
+    // followed everything we know about, try the constants stored right
+    // before unconditional jumps.
+    while !stored_constants.is_empty() {
+        let candidates: Vec<usize> = stored_constants.drain(..)
+                                                     .filter(|x| *x < memory.len() && claims[*x] == Claim::Unknown)
+                                                     .collect();
+        trace_from(set, memory, &candidates, &mut claims, &mut jump_targets, &mut stored_constants);
+    }
+
+    let mut lines = vec![];
+    let mut address = 0;
+
+    while address < memory.len() {
+        if claims[address] == Claim::Start {
+            let line = decode_at(set, memory, address);
+            address += line.size();
+            lines.push(line);
+        } else {
+            let mut values = vec![];
+            let start = address;
+
+            while address < memory.len() && claims[address] != Claim::Start && values.len() < DATA_PER_LINE {
+                values.push(memory[address]);
+                address += 1;
+            }
+            lines.push(Line::Data{ address: start, values });
+        }
+    }
+
+    Listing{ lines, jump_targets }
+}
+
+fn decode(set: &InstructionSet, value: i64) -> Option<(Instruction, &Operation)> {
+    let instruction = set.decode(value).ok()?;
+    Some((instruction, set.operation(instruction.opcode)?))
+}
+
+/// Decode the single instruction at the given address, without any of the
+/// analysis `disassemble` does. Anything that isn't a valid instruction in
+/// the set comes back as a single data cell.
+pub fn decode_at(set: &InstructionSet, memory: &[i64], address: usize) -> Line {
+    let value = memory.get(address).cloned().unwrap_or(0);
+
+    if let Some((instruction, operation)) = decode(set, value) {
+        let operands: Vec<Operand> = (0..operation.arity()).filter_map(|i| memory.get(address + i + 1))
+                                                           .zip(instruction.modes.iter())
+                                                           .map(|(value, mode)| Operand::new(*mode, *value))
+                                                           .collect();
+        if operands.len() == operation.arity() {
+            return Line::Code{ address, instruction, mnemonic: operation.mnemonic, roles: operation.roles, operands };
+        }
+    }
+
+    Line::Data{ address, values: vec![value] }
+}
+
+fn trace_from(set: &InstructionSet,
+              memory: &[i64],
+              entries: &[usize],
+              claims: &mut [Claim],
+              jump_targets: &mut BTreeSet<usize>,
+              stored_constants: &mut Vec<usize>)
+{
+    let mut worklist = entries.to_vec();
+
+    while let Some(mut address) = worklist.pop() {
+        let mut last_constant = None;
+
+        loop {
+            if address >= memory.len() || claims[address] != Claim::Unknown {
+                break;
+            }
+
+            let (instruction, operation) = match decode(set, memory[address]) {
+                Some(x) => x,
+                None    => break,
+            };
+            let end = address + operation.arity();
+
+            if end >= memory.len() || claims[address + 1..=end].iter().any(|x| *x != Claim::Unknown) {
+                break;
+            }
+            // A write to an immediate is something Computer::step refuses to
+            // do, so this can't really be code.
+            if operation.destination().map(|x| instruction.modes[x] == Mode::Immediate).unwrap_or(false) {
+                break;
+            }
+
+            claims[address] = Claim::Start;
+            for claim in claims[address + 1..=end].iter_mut() {
+                *claim = Claim::Argument;
+            }
+
+            if instruction.is_halt() {
+                break;
+            }
+
+            if instruction.is_jump() && instruction.modes[1] == Mode::Immediate && memory[address + 2] >= 0 {
+                let target = memory[address + 2] as usize;
+                jump_targets.insert(target);
+                worklist.push(target);
+                // A jump on a constant condition never falls through. If we
+                // just stored a constant, this was probably a call, and the
+                // constant is where it returns to.
+                if instruction.modes[0] == Mode::Immediate && instruction.jumps_on(memory[address + 1]) {
+                    stored_constants.extend(last_constant);
+                    break;
+                }
+            }
+
+            last_constant = None;
+            if instruction.modes[0] == Mode::Immediate && instruction.modes[1] == Mode::Immediate {
+                if let Some(value) = instruction.evaluate(memory[address + 1], memory[address + 2]) {
+                    if value >= 0 {
+                        last_constant = Some(value as usize);
+                    }
+                }
+            }
+
+            address = end + 1;
+        }
+    }
+}
+
+#[test]
+fn disassembly() {
+    let computer = Computer::from_string("1002,4,3,4,33");
+    let listing = disassemble(&computer, &[0]);
+    assert_eq!(format!("{}", listing.lines()[0]), "00000000: MULTIPLY [4], #3 -> [4]");
+    assert_eq!(format!("{}", listing.lines()[1]), "00000004: DATA 33");
+
+    let computer = Computer::from_string("109,1,204,-1,1001,100,1,100,1008,100,16,101,1006,101,0,99");
+    let text = format!("{}", disassemble(&computer, &[0]));
+    assert_eq!(text, "label_0:\n\
+                      00000000: ADJUST_BASE #1\n\
+                      00000002: OUTPUT rel[-1]\n\
+                      00000004: ADD [100], #1 -> [100]\n\
+                      00000008: EQUALS [100], #16 -> [101]\n\
+                      00000012: JMPNIF [101], #0\n\
+                      00000015: HALT\n");
+
+    let computer = Computer::from_string("3,9,8,9,10,9,4,9,99,-1,8");
+    let text = format!("{}", disassemble(&computer, &[0]));
+    assert_eq!(text, "00000000: INPUT -> [9]\n\
+                      00000002: EQUALS [9], [10] -> [9]\n\
+                      00000006: OUTPUT [9]\n\
+                      00000008: HALT\n\
+                      data_9:\n\
+                      00000009: DATA -1, 8\n");
+
+    // a dialect's instructions are code when disassembled with its set
+    let computer = Computer::from_string("104,3,10,0,99");
+    assert_eq!(format!("{}", disassemble(&computer, &[0]).lines()[1]), "00000002: DATA 10, 0, 99");
+    let computer = computer.with_instruction_set(std::sync::Arc::new(InstructionSet::debugging()));
+    assert_eq!(format!("{}", disassemble(&computer, &[0]).lines()[1]), "00000002: PRINT [0]");
+
+    // day 25 calls its subroutines by storing a return address and jumping;
+    // those return points should come out as code, and the strings as data.
+    let day25 = Computer::load("inputs/day25").unwrap();
+    let listing = disassemble(&day25, &[0]);
+    let is_code = |addr| listing.lines().iter().any(|l| l.address() == addr && matches!(l, Line::Code{..}));
+    let is_data = |addr| listing.lines().iter().any(|l| l.address() == addr && matches!(l, Line::Data{..}));
+    assert!(is_code(13));
+    assert!(is_code(31));
+    assert!(is_data(34));
+}
@@ -23,6 +23,15 @@ impl<T> Sender<T> {
     }
 }
 
+/// Dropping a sender closes the channel, so the other end doesn't hang.
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        if !self.done {
+            self.conclude();
+        }
+    }
+}
+
 pub struct Receiver<T> {
     underlying: mpsc::Receiver<Option<T>>,
     done: bool
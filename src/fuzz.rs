@@ -0,0 +1,288 @@
+use crate::device::Feeder;
+use crate::machine::{Computer, Instruction, Mode, RunResult};
+use crate::machine::{ADD, ADJUST_BASE, EQUALS, HALT, INPUT, JMPIF, JMPNIF, LESS_THAN, MULTIPLY, OUTPUT};
+use crate::memory::Memory;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fmt;
+
+/// Everything a random program might do, bar stopping.
+const OPCODES: [i64; 9] = [ADD, MULTIPLY, INPUT, OUTPUT, JMPIF, JMPNIF, LESS_THAN, EQUALS, ADJUST_BASE];
+
+/// Scratch cells after the code, for the program to read and write.
+const DATA_CELLS: usize = 16;
+
+/// How many instructions a random program gets to run, since plenty of them
+/// never halt.
+pub const FUZZ_BUDGET: u64 = 10_000;
+
+fn random_mode<R: Rng>(rng: &mut R, writes: bool) -> Mode {
+    match rng.gen_range(0, if writes { 2 } else { 3 }) {
+        0 => Mode::Position,
+        1 => Mode::Relative,
+        _ => Mode::Immediate,
+    }
+}
+
+fn random_value<R: Rng>(rng: &mut R) -> i64 {
+    // mostly small numbers, with the odd huge one to make things overflow
+    match rng.gen_range(0, 20) {
+        0 => i64::MAX - rng.gen_range(0, 100),
+        1 => i64::MIN + rng.gen_range(0, 100),
+        _ => rng.gen_range(-50, 50),
+    }
+}
+
+/// A random program that decodes cleanly: real opcodes, real modes, no
+/// immediate destinations, `length` instructions followed by a `HALT` and
+/// some data. Positions stay inside the program, so it may well rewrite its
+/// own code; the relative base only creeps about, so relative addresses
+/// mostly do too; and jumps only go to the start of an instruction.
+pub fn random_program<R: Rng>(rng: &mut R, length: usize) -> Vec<i64> {
+    let instructions: Vec<Instruction> = (0..length).map(|_| {
+        let opcode = OPCODES[rng.gen_range(0, OPCODES.len())];
+        let mut instruction = Instruction{ opcode, modes: [Mode::Position; 3] };
+        let (writes, arity) = (instruction.writes(), instruction.arity());
+        for (idx, mode) in instruction.modes.iter_mut().enumerate() {
+            *mode = random_mode(rng, writes && idx + 1 == arity);
+        }
+        if instruction.is_jump() {
+            instruction.modes[1] = Mode::Immediate;
+        }
+        instruction
+    }).collect();
+
+    let mut starts = vec![];
+    let mut size = 0;
+    for instruction in instructions.iter() {
+        starts.push(size as i64);
+        size += instruction.arity() + 1;
+    }
+    starts.push(size as i64);
+    let total = (size + 1 + DATA_CELLS) as i64;
+
+    let mut program = vec![];
+    for instruction in instructions.iter() {
+        program.push(instruction.encode());
+        for idx in 0..instruction.arity() {
+            let immediate = match instruction.opcode {
+                JMPIF | JMPNIF if idx == 1 => starts[rng.gen_range(0, starts.len())],
+                JMPIF | JMPNIF             => rng.gen_range(0, 2),
+                ADJUST_BASE                => rng.gen_range(-4, 5),
+                _                          => random_value(rng),
+            };
+            program.push(match instruction.modes[idx] {
+                Mode::Position | Mode::Relative => rng.gen_range(0, total),
+                Mode::Immediate                 => immediate,
+            });
+        }
+    }
+    program.push(HALT);
+    program.extend((0..DATA_CELLS).map(|_| random_value(rng)));
+    program
+}
+
+/// How a run of a machine came out.
+#[derive(Debug)]
+pub enum Ending {
+    Halted(Computer),
+    /// It wanted more input than there was.
+    Starved(Computer),
+    OutOfBudget(Computer),
+    Failed(String),
+}
+
+impl Ending {
+    fn computer(&self) -> Option<&Computer> {
+        match self {
+            Ending::Halted(x) | Ending::Starved(x) | Ending::OutOfBudget(x) => Some(x),
+            Ending::Failed(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Ending {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ending::Halted(_)      => write!(f, "halted"),
+            Ending::Starved(_)     => write!(f, "ran out of input"),
+            Ending::OutOfBudget(_) => write!(f, "ran out of budget"),
+            Ending::Failed(e)      => write!(f, "failed ({})", e),
+        }
+    }
+}
+
+pub struct Run {
+    pub outputs: Vec<i64>,
+    pub ending: Ending,
+}
+
+/// A way of running a machine on some inputs, until it stops.
+pub type Engine = fn(Computer, &[i64]) -> Run;
+
+/// Run the machine on the inputs with the given engine, noting how it ended.
+fn feed<'a, F>(inputs: &'a [i64], engine: F) -> Run
+  where F: FnOnce(&mut Feeder<'a>) -> RunResult
+{
+    let mut feeder = Feeder::new(inputs);
+
+    let ending = match engine(&mut feeder) {
+        RunResult::Input(next)       => Ending::Starved(next),
+        RunResult::Halted(next)      => Ending::Halted(next),
+        RunResult::OutOfBudget(next) => Ending::OutOfBudget(next),
+        RunResult::Error(e)          => Ending::Failed(e.to_string()),
+        RunResult::Output(..) | RunResult::Continue(_) =>
+            unreachable!("the feeder never stops the machine"),
+    };
+    Run{ outputs: feeder.outputs, ending }
+}
+
+/// The reference engine: `Computer::step_with`, one `step` at a time.
+pub fn stepped(computer: Computer, inputs: &[i64]) -> Run {
+    feed(inputs, |feeder| computer.step_with(feeder))
+}
+
+/// The fast engine: `Computer::run_with`.
+pub fn fast(computer: Computer, inputs: &[i64]) -> Run {
+    feed(inputs, |feeder| computer.run_with(feeder))
+}
+
+/// The first way two runs of the same program came out differently.
+#[derive(Debug, PartialEq)]
+pub enum Divergence {
+    /// The outputs differ at this index; `None` means that run had stopped.
+    Output(usize, Option<i64>, Option<i64>),
+    Ending(String, String),
+    Position(usize, usize),
+    RelativeBase(i64, i64),
+    Memory(usize, i64, i64),
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let output = |x: &Option<i64>| x.map(|x| x.to_string()).unwrap_or_else(|| "nothing".to_string());
+        match self {
+            Divergence::Output(idx, a, b)   => write!(f, "output {}: {} vs {}", idx, output(a), output(b)),
+            Divergence::Ending(a, b)        => write!(f, "one {}, the other {}", a, b),
+            Divergence::Position(a, b)      => write!(f, "position: {} vs {}", a, b),
+            Divergence::RelativeBase(a, b)  => write!(f, "relative base: {} vs {}", a, b),
+            Divergence::Memory(addr, a, b)  => write!(f, "memory at {}: {} vs {}", addr, a, b),
+        }
+    }
+}
+
+fn first_memory_difference(a: &Memory, b: &Memory) -> Option<(usize, i64, i64)> {
+    let length = a.image().len().max(b.image().len());
+    let mut sparse = a.sparse_cells();
+    sparse.extend(b.sparse_cells());
+
+    (0..length).chain(sparse.keys().cloned().filter(|x| *x >= length))
+               .map(|addr| (addr, a.get(addr), b.get(addr)))
+               .find(|(_, x, y)| x != y)
+}
+
+/// Run the same machine on the same inputs with both engines, and find the
+/// first thing they disagree on, if anything: outputs, how the run ended,
+/// and then the state of the machine.
+pub fn compare(computer: &Computer, inputs: &[i64], a: Engine, b: Engine) -> Option<Divergence> {
+    let first = a(computer.clone(), inputs);
+    let second = b(computer.clone(), inputs);
+
+    let outputs = first.outputs.len().max(second.outputs.len());
+    if let Some(idx) = (0..outputs).find(|x| first.outputs.get(*x) != second.outputs.get(*x)) {
+        return Some(Divergence::Output(idx, first.outputs.get(idx).cloned(), second.outputs.get(idx).cloned()));
+    }
+
+    let (first_end, second_end) = (first.ending.to_string(), second.ending.to_string());
+    let (x, y) = match (first.ending.computer(), second.ending.computer()) {
+        (Some(x), Some(y)) if first_end == second_end => (x, y),
+        _ if first_end == second_end                  => return None,
+        _                                             => return Some(Divergence::Ending(first_end, second_end)),
+    };
+
+    if x.position() != y.position() {
+        return Some(Divergence::Position(x.position(), y.position()));
+    }
+    if x.relative_base() != y.relative_base() {
+        return Some(Divergence::RelativeBase(x.relative_base(), y.relative_base()));
+    }
+    first_memory_difference(x.full_memory(), y.full_memory()).map(|(addr, a, b)| Divergence::Memory(addr, a, b))
+}
+
+/// A program the engines disagreed on, and how.
+pub struct Failure {
+    pub program: Vec<i64>,
+    pub inputs: Vec<i64>,
+    pub divergence: Divergence,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let join = |x: &[i64]| x.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(",");
+        writeln!(f, "Engines disagree: {}", self.divergence)?;
+        writeln!(f, "Program: {}", join(&self.program))?;
+        write!(f, "Inputs: {}", join(&self.inputs))
+    }
+}
+
+/// Compare the two engines on the given number of random programs, with
+/// random inputs, starting from the given seed. Comes back with the first
+/// program they disagree on.
+pub fn fuzz(seed: u64, count: usize, a: Engine, b: Engine) -> Result<(), Failure> {
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    for _ in 0..count {
+        let length = rng.gen_range(1, 40);
+        let program = random_program(&mut rng, length);
+        let inputs: Vec<i64> = (0..rng.gen_range(0, 8)).map(|_| random_value(&mut rng)).collect();
+        let computer = Computer::from_state(Memory::from(program.clone()), 0, 0, false, None).with_step_budget(FUZZ_BUDGET);
+
+        if let Some(divergence) = compare(&computer, &inputs, a, b) {
+            return Err(Failure{ program, inputs, divergence });
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn fuzzing() {
+    // random programs are always well formed
+    let mut rng = StdRng::seed_from_u64(7);
+    for _ in 0..50 {
+        let program = random_program(&mut rng, 20);
+        let mut address = 0;
+        for _ in 0..20 {
+            let instruction = Instruction::decode(program[address]).unwrap();
+            assert!(!instruction.writes() || instruction.modes[instruction.arity() - 1] != Mode::Immediate);
+            address += instruction.arity() + 1;
+        }
+        assert_eq!(program[address], HALT);
+        assert_eq!(program.len(), address + 1 + DATA_CELLS);
+    }
+
+    assert!(fuzz(1, 500, stepped, fast).is_ok());
+
+    // an engine that gets one thing wrong gets caught
+    fn forgetful(computer: Computer, inputs: &[i64]) -> Run {
+        let mut run = fast(computer, inputs);
+        run.outputs.truncate(1);
+        run
+    }
+    fn scribbler(computer: Computer, inputs: &[i64]) -> Run {
+        let mut run = fast(computer, inputs);
+        if let Ending::Halted(computer) = &mut run.ending {
+            computer.write(2000, 1).unwrap();
+        }
+        run
+    }
+    let echo = Computer::from_string("3,9,4,9,4,9,99,0,0,0");
+    assert_eq!(compare(&echo, &[5], stepped, fast), None);
+    assert_eq!(compare(&echo, &[5], stepped, forgetful), Some(Divergence::Output(1, Some(5), None)));
+    assert_eq!(compare(&echo, &[5], stepped, scribbler), Some(Divergence::Memory(2000, 0, 1)));
+    assert_eq!(compare(&echo, &[], stepped, scribbler), None);
+    let failure = fuzz(1, 500, stepped, forgetful).err().unwrap();
+    assert!(matches!(failure.divergence, Divergence::Output(1, Some(_), None)));
+    assert_eq!(compare(&Computer::from_state(Memory::from(failure.program), 0, 0, false, None).with_step_budget(FUZZ_BUDGET),
+                       &failure.inputs, stepped, fast), None);
+}
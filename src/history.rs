@@ -0,0 +1,177 @@
+use crate::machine::{Computer, Fault, Registers, RunResult};
+use crate::transcript::Io;
+use std::collections::VecDeque;
+
+/// How many steps a history remembers, unless told otherwise.
+pub const DEFAULT_HISTORY: usize = 1_000_000;
+
+/// What a single step changed, and what it took to get there.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Change {
+    before: Registers,
+    /// The address the step wrote to, and what used to be there.
+    write: Option<(usize, i64)>,
+    io: Option<Io>,
+}
+
+/// What undoing a step put back.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Undone {
+    pub write: Option<usize>,
+    pub io: Option<Io>,
+}
+
+/// The recent past of a machine, kept as the registers and the one memory
+/// cell each step changed, rather than a copy of the whole machine per step.
+/// Run the machine through `step` and `provide_input`, and change its memory
+/// through `write`, to have it recorded; the oldest steps fall off once there
+/// are more than the limit.
+#[derive(Debug)]
+pub struct History {
+    changes: VecDeque<Change>,
+    limit: usize,
+}
+
+impl History {
+    pub fn new(limit: usize) -> History {
+        History{ changes: VecDeque::new(), limit }
+    }
+
+    /// How many steps we could undo.
+    pub fn steps(&self) -> usize {
+        self.changes.len()
+    }
+
+    fn remember(&mut self, change: Change) {
+        if self.limit == 0 {
+            return;
+        }
+        if self.changes.len() == self.limit {
+            self.changes.pop_front();
+        }
+        self.changes.push_back(change);
+    }
+
+    fn about_to_change(computer: &Computer, io: Option<Io>) -> Change {
+        let write = computer.destination().map(|x| (x, computer.read(x)));
+        Change{ before: computer.registers(), write, io }
+    }
+
+    /// `Computer::step`, remembering how to undo it. Stopping at an `INPUT`
+    /// doesn't count as a step, and nor does running out of budget; reading
+    /// the value does.
+    pub fn step(&mut self, computer: Computer) -> RunResult {
+        let mut change = History::about_to_change(&computer, None);
+
+        match computer.step() {
+            RunResult::Input(next) => RunResult::Input(next),
+            RunResult::Error(e)    => RunResult::Error(e),
+            RunResult::OutOfBudget(next) => RunResult::OutOfBudget(next),
+            RunResult::Continue(next) => {
+                self.remember(change);
+                RunResult::Continue(next)
+            }
+            RunResult::Halted(next) => {
+                self.remember(change);
+                RunResult::Halted(next)
+            }
+            RunResult::Output(value, next) => {
+                change.io = Some(Io::Output(value));
+                self.remember(change);
+                RunResult::Output(value, next)
+            }
+        }
+    }
+
+    /// `Computer::provide_input`, remembering how to undo it.
+    pub fn provide_input(&mut self, computer: Computer, value: i64) -> Computer {
+        self.remember(History::about_to_change(&computer, Some(Io::Input(value))));
+        computer.provide_input(value)
+    }
+
+    /// `Computer::write`, remembering how to undo it. Undoing it counts as
+    /// going back a step.
+    pub fn write(&mut self, computer: &mut Computer, address: usize, value: i64) -> Result<(), Fault> {
+        let change = Change{ before: computer.registers(), write: Some((address, computer.read(address))), io: None };
+        computer.write(address, value)?;
+        self.remember(change);
+        Ok(())
+    }
+
+    /// Put the machine back the way it was before its last remembered step,
+    /// if there is one.
+    pub fn undo(&mut self, computer: &mut Computer) -> Option<Undone> {
+        let change = self.changes.pop_back()?;
+
+        if let Some((address, value)) = change.write {
+            computer.write(address, value).expect("undid a write that never happened");
+        }
+        computer.set_registers(change.before);
+        Some(Undone{ write: change.write.map(|(address, _)| address), io: change.io })
+    }
+}
+
+#[test]
+fn history() {
+    let mut history = History::new(DEFAULT_HISTORY);
+    let mut computer = Computer::from_string("3,11,8,11,12,11,4,11,109,5,99,0,8");
+    let mut states = vec![computer.clone()];
+
+    while !computer.is_done() {
+        computer = match history.step(computer) {
+            RunResult::Input(next)     => history.provide_input(next, 8),
+            RunResult::Continue(next)  => next,
+            RunResult::Output(_, next) => next,
+            RunResult::Halted(next)    => next,
+            RunResult::Error(e)        => panic!("history test failed: {}", e),
+            RunResult::OutOfBudget(_)  => panic!("history test ran out of budget"),
+        };
+        states.push(computer.clone());
+    }
+    assert_eq!(history.steps(), 5);
+    assert_eq!(computer.read(11), 1);
+
+    // going backwards visits every state we went through on the way
+    states.pop();
+    let undone = history.undo(&mut computer).unwrap();
+    assert_eq!(undone, Undone{ write: None, io: None });
+    assert_eq!(&computer, states.last().unwrap());
+    states.pop();
+    assert_eq!(history.undo(&mut computer).unwrap().io, None);
+    assert_eq!(&computer, states.last().unwrap());
+    states.pop();
+    assert_eq!(history.undo(&mut computer).unwrap().io, Some(Io::Output(1)));
+    assert_eq!(&computer, states.last().unwrap());
+    states.pop();
+    assert_eq!(history.undo(&mut computer).unwrap().write, Some(11));
+    assert_eq!(&computer, states.last().unwrap());
+    assert_eq!(computer.read(11), 8);
+    states.pop();
+    assert_eq!(history.undo(&mut computer).unwrap(), Undone{ write: Some(11), io: Some(Io::Input(8)) });
+    assert_eq!(computer.awaiting_input(), Some(11));
+    assert_eq!(computer.read(11), 0);
+    assert_eq!(history.undo(&mut computer), None);
+
+    // so are writes from outside the machine
+    history.write(&mut computer, 11, 7).unwrap();
+    assert_eq!(history.steps(), 1);
+    assert_eq!(history.undo(&mut computer).unwrap(), Undone{ write: Some(11), io: None });
+    assert_eq!(computer.read(11), 0);
+
+    // only the most recent steps are kept
+    let mut history = History::new(2);
+    let mut computer = Computer::from_string("1101,1,1,20,1101,2,2,21,1101,3,3,22,99");
+    for _ in 0..3 {
+        computer = match history.step(computer) {
+            RunResult::Continue(next) => next,
+            _                         => panic!("history test stopped early"),
+        };
+    }
+    assert_eq!(history.steps(), 2);
+    assert!(history.undo(&mut computer).is_some());
+    assert!(history.undo(&mut computer).is_some());
+    assert_eq!(history.undo(&mut computer), None);
+    assert_eq!(computer.position(), 4);
+    assert_eq!(computer.read(20), 2);
+    assert_eq!(computer.read(21), 0);
+}
@@ -0,0 +1,218 @@
+use crate::machine::{Computer, Fault, Instruction};
+use crate::machine::{ADD, ADJUST_BASE, EQUALS, HALT, INPUT, JMPIF, JMPNIF, LESS_THAN, MULTIPLY, OUTPUT};
+use std::sync::OnceLock;
+
+/// Opcodes are the last two digits of an instruction word.
+const OPCODE_LIMIT: i64 = 100;
+
+/// The opcode `debugging` adds for `PRINT`.
+pub const PRINT: i64 = 10;
+
+/// What an instruction does with one of its arguments.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Role {
+    /// The argument is a value to read, in any mode.
+    Read,
+    /// The argument is the address to write the result to.
+    Write,
+}
+
+/// What the machine should do once an operation has looked at its
+/// arguments, beyond moving on to the next instruction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Action {
+    Continue,
+    /// Store this at the instruction's destination.
+    Write(i64),
+    Output(i64),
+    /// Jump to this address, if there is one, rather than moving on.
+    Jump(Option<i64>),
+    /// Move the relative base here.
+    Base(i64),
+    /// Stop until there's a value to store at the instruction's destination.
+    Input,
+    Halt,
+}
+
+/// What an operation does, given the machine and the values of the
+/// arguments it reads, in order.
+pub type Semantics = fn(&Computer, &[i64]) -> Result<Action, Fault>;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Operation {
+    pub mnemonic: &'static str,
+    pub roles: &'static [Role],
+    pub semantics: Semantics,
+}
+
+impl Operation {
+    pub fn arity(&self) -> usize {
+        self.roles.len()
+    }
+
+    /// Where the destination is among the arguments, if there is one.
+    pub fn destination(&self) -> Option<usize> {
+        self.roles.iter().position(|x| *x == Role::Write)
+    }
+}
+
+/// The opcodes a computer understands, and what each of them does. Start
+/// from `standard` and add to it with `with` to try out a dialect, and hand
+/// it to `Computer::with_instruction_set`.
+#[derive(Clone, Debug)]
+pub struct InstructionSet {
+    operations: Vec<Option<Operation>>,
+}
+
+impl InstructionSet {
+    /// An instruction set with no instructions at all.
+    pub fn empty() -> InstructionSet {
+        InstructionSet{ operations: vec![None; OPCODE_LIMIT as usize] }
+    }
+
+    /// The instructions from the puzzles, which every computer runs unless
+    /// it's told otherwise.
+    pub fn standard() -> &'static InstructionSet {
+        static STANDARD: OnceLock<InstructionSet> = OnceLock::new();
+        STANDARD.get_or_init(|| {
+            use Role::{Read, Write};
+
+            InstructionSet::empty().with(ADD,         "ADD",         &[Read, Read, Write], add)
+                                   .with(MULTIPLY,    "MULTIPLY",    &[Read, Read, Write], multiply)
+                                   .with(INPUT,       "INPUT",       &[Write],             input)
+                                   .with(OUTPUT,      "OUTPUT",      &[Read],              output)
+                                   .with(JMPIF,       "JMPIF",       &[Read, Read],        jump_if)
+                                   .with(JMPNIF,      "JMPNIF",      &[Read, Read],        jump_if_not)
+                                   .with(LESS_THAN,   "LESS_THAN",   &[Read, Read, Write], less_than)
+                                   .with(EQUALS,      "EQUALS",      &[Read, Read, Write], equals)
+                                   .with(ADJUST_BASE, "ADJUST_BASE", &[Read],              adjust_base)
+                                   .with(HALT,        "HALT",        &[],                  halt)
+        })
+    }
+
+    /// The standard instructions, plus `PRINT`, which writes its one
+    /// argument to stderr and carries on.
+    pub fn debugging() -> InstructionSet {
+        InstructionSet::standard().clone().with(PRINT, "PRINT", &[Role::Read], print)
+    }
+
+    /// This instruction set, with the given opcode doing something new. An
+    /// opcode that's already there is replaced. Panics if the opcode isn't
+    /// two digits, or the operation has more than three arguments or more
+    /// than one destination, since instruction words can't describe those.
+    pub fn with(mut self, opcode: i64, mnemonic: &'static str, roles: &'static [Role], semantics: Semantics) -> InstructionSet {
+        assert!((0..OPCODE_LIMIT).contains(&opcode), "opcode {} isn't two digits", opcode);
+        assert!(roles.len() <= 3, "{} has more than three arguments", mnemonic);
+        assert!(roles.iter().filter(|x| **x == Role::Write).count() <= 1, "{} has more than one destination", mnemonic);
+        self.operations[opcode as usize] = Some(Operation{ mnemonic, roles, semantics });
+        self
+    }
+
+    pub fn operation(&self, opcode: i64) -> Option<&Operation> {
+        if !(0..OPCODE_LIMIT).contains(&opcode) {
+            return None;
+        }
+        self.operations[opcode as usize].as_ref()
+    }
+
+    /// Every opcode in the set, in order, with its operation.
+    pub fn operations(&self) -> impl Iterator<Item = (i64, &Operation)> {
+        self.operations.iter().enumerate().filter_map(|(opcode, x)| x.as_ref().map(|x| (opcode as i64, x)))
+    }
+
+    pub fn decode(&self, value: i64) -> Result<Instruction, Fault> {
+        let instruction = Instruction::split(value)?;
+        match self.operation(instruction.opcode) {
+            Some(_) => Ok(instruction),
+            None    => Err(Fault::UnknownOpcode(instruction.opcode)),
+        }
+    }
+}
+
+fn add(computer: &Computer, args: &[i64]) -> Result<Action, Fault> {
+    computer.arithmetic().add(args[0], args[1]).map(Action::Write)
+}
+
+fn multiply(computer: &Computer, args: &[i64]) -> Result<Action, Fault> {
+    computer.arithmetic().multiply(args[0], args[1]).map(Action::Write)
+}
+
+fn input(_: &Computer, _: &[i64]) -> Result<Action, Fault> {
+    Ok(Action::Input)
+}
+
+fn output(_: &Computer, args: &[i64]) -> Result<Action, Fault> {
+    Ok(Action::Output(args[0]))
+}
+
+fn jump_if(_: &Computer, args: &[i64]) -> Result<Action, Fault> {
+    Ok(Action::Jump(if args[0] != 0 { Some(args[1]) } else { None }))
+}
+
+fn jump_if_not(_: &Computer, args: &[i64]) -> Result<Action, Fault> {
+    Ok(Action::Jump(if args[0] == 0 { Some(args[1]) } else { None }))
+}
+
+fn less_than(_: &Computer, args: &[i64]) -> Result<Action, Fault> {
+    Ok(Action::Write(if args[0] < args[1] { 1 } else { 0 }))
+}
+
+fn equals(_: &Computer, args: &[i64]) -> Result<Action, Fault> {
+    Ok(Action::Write(if args[0] == args[1] { 1 } else { 0 }))
+}
+
+fn adjust_base(computer: &Computer, args: &[i64]) -> Result<Action, Fault> {
+    computer.arithmetic().add(computer.relative_base(), args[0]).map(Action::Base)
+}
+
+fn halt(_: &Computer, _: &[i64]) -> Result<Action, Fault> {
+    Ok(Action::Halt)
+}
+
+fn print(computer: &Computer, args: &[i64]) -> Result<Action, Fault> {
+    eprintln!("PRINT at {}: {}", computer.position(), args[0]);
+    Ok(Action::Continue)
+}
+
+#[test]
+fn dialects() {
+    use crate::machine::{MachineError, RunResult};
+    use crate::trace::Profiler;
+    use std::sync::{Arc, Mutex};
+
+    fn square(computer: &Computer, args: &[i64]) -> Result<Action, Fault> {
+        computer.arithmetic().multiply(args[0], args[0]).map(Action::Write)
+    }
+    fn store(_: &Computer, args: &[i64]) -> Result<Action, Fault> {
+        Ok(Action::Write(args[0]))
+    }
+
+    let standard = InstructionSet::standard();
+    assert_eq!(standard.operations().count(), 10);
+    assert_eq!(standard.operation(ADD).unwrap().destination(), Some(2));
+    assert!(standard.operation(PRINT).is_none());
+    assert!(matches!(standard.decode(-5), Err(Fault::UnknownOpcode(-5))));
+    assert_eq!(Instruction::from_mnemonic("EQUALS").map(|x| x.opcode), Some(EQUALS));
+
+    // SQUARE [11] -> [12]; STORE -> [13], [12]; OUTPUT [13]; PRINT [12]; HALT
+    let program = Computer::from_string("20,11,12,21,13,12,4,13,10,12,99,7,0,0");
+    assert!(matches!(program.clone().run(), RunResult::Error(MachineError::Fault(0, 20, Fault::UnknownOpcode(20)))));
+    assert_eq!(program.clone().with_instruction_set(Arc::new(standard.clone())), program);
+    assert_ne!(program.clone().with_instruction_set(Arc::new(InstructionSet::debugging())), program);
+
+    use Role::{Read, Write};
+    let dialect = InstructionSet::debugging().with(20, "SQUARE", &[Read, Write], square)
+                                             .with(21, "STORE",  &[Write, Read], store);
+    assert_eq!(dialect.operation(21).unwrap().destination(), Some(0));
+    let profiler = Arc::new(Mutex::new(Profiler::new()));
+    let computer = program.with_instruction_set(Arc::new(dialect)).with_tracer(profiler.clone());
+    let computer = match computer.step() {
+        RunResult::Continue(next) => next,
+        _                         => panic!("SQUARE didn't run"),
+    };
+    assert_eq!(computer.read(12), 49);
+    assert_eq!(computer.destination(), Some(13));
+    assert_eq!(computer.standard_run(&[]), vec![49]);
+    let counts: Vec<(i64, u64)> = profiler.lock().unwrap().opcode_counts().iter().map(|(x, y)| (*x, *y)).collect();
+    assert_eq!(counts, vec![(OUTPUT, 1), (PRINT, 1), (20, 1), (21, 1), (HALT, 1)]);
+}
@@ -0,0 +1,117 @@
+use crate::machine::MachineError;
+use std::convert::TryInto;
+use std::iter;
+
+/// The ways we can write a program down.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    /// Numbers separated by commas and/or whitespace, with comments.
+    Text,
+    /// `BINARY_HEADER`, then each cell as eight little-endian bytes, one
+    /// after another.
+    Binary,
+}
+
+/// What every binary program starts with. It's a whole cell long, so the
+/// cells after it stay lined up, and no program text can start with it.
+pub const BINARY_HEADER: &[u8; 8] = b"INTCODE\0";
+
+/// Whether the given file contents are a binary program, going by the
+/// header.
+pub fn is_binary(bytes: &[u8]) -> bool {
+    bytes.starts_with(BINARY_HEADER)
+}
+
+/// Read program text: numbers separated by commas, whitespace, newlines, or
+/// any mix of them, with anything from a `#` to the end of its line ignored.
+/// Stray separators, like a trailing comma, don't matter.
+pub fn parse_text(text: &str) -> Result<Vec<i64>, MachineError> {
+    let mut result = vec![];
+
+    for (line_idx, line) in text.lines().enumerate() {
+        let code = line.split('#').next().unwrap_or("");
+        let mut start = None;
+
+        for (idx, c) in code.char_indices().chain(iter::once((code.len(), ','))) {
+            if c != ',' && !c.is_whitespace() {
+                start = start.or(Some(idx));
+                continue;
+            }
+            if let Some(start) = start.take() {
+                let word = &code[start..idx];
+                match word.parse::<i64>() {
+                    Ok(value) => result.push(value),
+                    Err(_)    => return Err(MachineError::Parse(line_idx + 1, code[..start].chars().count() + 1, word.to_string())),
+                }
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+pub fn to_text(image: &[i64]) -> String {
+    let values: Vec<String> = image.iter().map(|x| x.to_string()).collect();
+    format!("{}\n", values.join(","))
+}
+
+pub fn parse_binary(bytes: &[u8]) -> Result<Vec<i64>, MachineError> {
+    let body = match bytes.strip_prefix(&BINARY_HEADER[..]) {
+        Some(body) => body,
+        None       => return Err(MachineError::Binary("it doesn't start with the binary header".to_string())),
+    };
+    let cells = body.chunks_exact(8);
+    if !cells.remainder().is_empty() {
+        return Err(MachineError::Binary(format!("{} bytes of cells isn't a whole number of them", body.len())));
+    }
+    Ok(cells.map(|x| i64::from_le_bytes(x.try_into().unwrap())).collect())
+}
+
+pub fn to_binary(image: &[i64]) -> Vec<u8> {
+    BINARY_HEADER.iter().cloned().chain(image.iter().flat_map(|x| x.to_le_bytes().to_vec())).collect()
+}
+
+#[test]
+fn loading() {
+    assert_eq!(parse_text("1,0,0,0,99").unwrap(), vec![1, 0, 0, 0, 99]);
+    assert_eq!(parse_text("1,0,0,0,99,\n").unwrap(), vec![1, 0, 0, 0, 99]);
+    assert_eq!(parse_text("1 0 0\t0\n99").unwrap(), vec![1, 0, 0, 0, 99]);
+    assert_eq!(parse_text("1, 0, 0, 0,\r\n99\r\n").unwrap(), vec![1, 0, 0, 0, 99]);
+    assert_eq!(parse_text("# adds two numbers\n1,0,0,0 # into the first cell\n99\n").unwrap(), vec![1, 0, 0, 0, 99]);
+    assert_eq!(parse_text("").unwrap(), Vec::<i64>::new());
+    assert_eq!(parse_text("-5,+7").unwrap(), vec![-5, 7]);
+
+    match parse_text("1,0,0,0\n  99, x2, 3") {
+        Err(MachineError::Parse(2, 7, word)) => assert_eq!(word, "x2"),
+        _ => panic!("bad value wasn't caught"),
+    }
+    let error = parse_text("1,2,99999999999999999999").unwrap_err();
+    assert_eq!(error.to_string(), "Line 1, column 5: '99999999999999999999' isn't a number");
+
+    let image = vec![1, -1, i64::MAX, 0, 99];
+    let bytes = to_binary(&image);
+    assert_eq!(bytes.len(), 48);
+    assert_eq!(&bytes[8..24], &[1, 0, 0, 0, 0, 0, 0, 0, 255, 255, 255, 255, 255, 255, 255, 255]);
+    assert!(is_binary(&bytes));
+    assert!(!is_binary(to_text(&image).as_bytes()));
+    assert_eq!(parse_binary(&bytes).unwrap(), image);
+    assert_eq!(parse_binary(&bytes[..20]).unwrap_err().to_string(),
+               "Bad binary program: 12 bytes of cells isn't a whole number of them");
+    assert!(matches!(parse_binary(&bytes[8..]), Err(MachineError::Binary(_))));
+    // no zero bytes at all, but still binary
+    assert!(is_binary(&to_binary(&[-1])));
+    assert_eq!(parse_binary(&to_binary(&[-1])).unwrap(), vec![-1]);
+    assert_eq!(parse_text(&to_text(&image)).unwrap(), image);
+
+    // computers go out and back in either way
+    use crate::machine::Computer;
+    let day9 = Computer::load("inputs/day9").unwrap();
+    for (format, name) in [(Format::Text, "txt"), (Format::Binary, "bin")].iter() {
+        let path = std::env::temp_dir().join(format!("aoc-loader-{}.{}", std::process::id(), name));
+        let path = path.to_str().unwrap();
+        day9.save(path, *format).unwrap();
+        let loaded = Computer::load(path);
+        let _ = std::fs::remove_file(path);
+        assert_eq!(loaded.unwrap(), day9);
+    }
+}
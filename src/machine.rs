@@ -1,371 +1,876 @@
-use std::collections::VecDeque;
+use crate::device::{Device, Feeder, Flow};
+use crate::isa::{Action, InstructionSet, Role};
+use crate::loader::{self, Format};
+use crate::memory::Memory;
+use crate::trace::{Effect, Event, Tracer};
+use std::convert::TryFrom;
+use std::fmt;
 use std::fs;
-use std::iter::FromIterator;
-use std::ops::Range;
+use std::io;
 use std::str;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-const ADD: i64         = 1;
-const MULTIPLY: i64    = 2;
-const INPUT: i64       = 3;
-const OUTPUT: i64      = 4;
-const JMPIF: i64       = 5;
-const JMPNIF: i64      = 6;
-const LESS_THAN: i64   = 7;
-const EQUALS: i64      = 8;
-const ADJUST_BASE: i64 = 9;
-const HALT: i64        = 99;
-
-#[derive(Debug,PartialEq)]
+pub const ADD: i64         = 1;
+pub const MULTIPLY: i64    = 2;
+pub const INPUT: i64       = 3;
+pub const OUTPUT: i64      = 4;
+pub const JMPIF: i64       = 5;
+pub const JMPNIF: i64      = 6;
+pub const LESS_THAN: i64   = 7;
+pub const EQUALS: i64      = 8;
+pub const ADJUST_BASE: i64 = 9;
+pub const HALT: i64        = 99;
+
+/// How many instructions to run between looks at the clock, when there's a
+/// time limit; looking every instruction would cost more than running them.
+const CLOCK_INTERVAL: u32 = 4096;
+
+#[derive(Clone,Copy,Debug,PartialEq)]
 pub enum Mode {
     Position,
     Immediate,
     Relative,
 }
 
-impl From<i64> for Mode {
-    fn from(x: i64) -> Mode {
+impl TryFrom<i64> for Mode {
+    type Error = Fault;
+
+    fn try_from(x: i64) -> Result<Mode, Fault> {
         match x {
-            0 => Mode::Position,
-            1 => Mode::Immediate,
-            2 => Mode::Relative,
-            _ => panic!("Unknown mode value: {}", x),
+            0 => Ok(Mode::Position),
+            1 => Ok(Mode::Immediate),
+            2 => Ok(Mode::Relative),
+            _ => Err(Fault::UnknownMode(x)),
+        }
+    }
+}
+
+/// The things that can go wrong while executing a single instruction.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Fault {
+    UnknownMode(i64),
+    UnknownOpcode(i64),
+    ImmediateDestination,
+    NegativeAddress(i64),
+    OutOfMemory(usize, usize),
+    /// Adding or multiplying these two didn't fit in a cell.
+    Overflow(i64, i64),
+}
+
+/// What `ADD` and `MULTIPLY` do with a result that doesn't fit in a cell.
+/// For cells that never run out, there's `BigComputer`.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Arithmetic {
+    /// Wrap around, as two's complement does.
+    #[default]
+    Wrapping,
+    /// Stop the machine with `Fault::Overflow`.
+    Checked,
+}
+
+impl Arithmetic {
+    pub fn add(self, arg1: i64, arg2: i64) -> Result<i64, Fault> {
+        match self {
+            Arithmetic::Wrapping => Ok(arg1.wrapping_add(arg2)),
+            Arithmetic::Checked  => arg1.checked_add(arg2).ok_or(Fault::Overflow(arg1, arg2)),
+        }
+    }
+
+    pub fn multiply(self, arg1: i64, arg2: i64) -> Result<i64, Fault> {
+        match self {
+            Arithmetic::Wrapping => Ok(arg1.wrapping_mul(arg2)),
+            Arithmetic::Checked  => arg1.checked_mul(arg2).ok_or(Fault::Overflow(arg1, arg2)),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum MachineError {
+    /// We couldn't read the program file.
+    Io(io::Error),
+    /// The program file wasn't text.
+    Encoding(str::Utf8Error),
+    /// The word at the given line and column of the program text wasn't a
+    /// number.
+    Parse(usize, usize, String),
+    /// A binary program didn't make sense, for the given reason.
+    Binary(String),
+    /// The instruction at the given address, with the given value, failed.
+    Fault(usize, i64, Fault),
+    /// The given line of a saved snapshot didn't make sense.
+    Snapshot(usize, String),
+    /// A snapshot can't hold this machine, for the given reason.
+    Unsaveable(String),
+    /// The given line of a saved transcript didn't make sense.
+    Transcript(usize, String),
+}
+
+impl From<io::Error> for MachineError {
+    fn from(x: io::Error) -> MachineError {
+        MachineError::Io(x)
+    }
+}
+
+impl From<str::Utf8Error> for MachineError {
+    fn from(x: str::Utf8Error) -> MachineError {
+        MachineError::Encoding(x)
+    }
+}
+
+/// A decoded instruction word: the opcode plus the addressing modes of its
+/// (up to) three arguments. This is the one place that knows how the digits
+/// of an instruction are laid out, so everything that wants to look at a
+/// program should go through `Instruction::decode`.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct Instruction {
+    pub opcode: i64,
+    pub modes: [Mode; 3],
+}
+
+impl Instruction {
+    /// Decode an instruction word from the standard instruction set.
+    pub fn decode(value: i64) -> Result<Instruction, Fault> {
+        InstructionSet::standard().decode(value)
+    }
+
+    /// Split an instruction word into its opcode and modes, without
+    /// checking the opcode means anything.
+    pub fn split(value: i64) -> Result<Instruction, Fault> {
+        let opcode = value % 100;
+        let mut modes = [Mode::Position; 3];
+
+        for (idx, digit) in [(value / 100) % 10, (value / 1000) % 10, (value / 10000) % 10].iter().enumerate() {
+            modes[idx] = Mode::try_from(*digit)?;
+        }
+
+        Ok(Instruction{ opcode, modes })
+    }
+
+    /// Build the instruction with the given mnemonic, with every argument in
+    /// position mode.
+    pub fn from_mnemonic(mnemonic: &str) -> Option<Instruction> {
+        let (opcode, _) = InstructionSet::standard().operations().find(|(_, x)| x.mnemonic == mnemonic)?;
+        Some(Instruction{ opcode, modes: [Mode::Position; 3] })
+    }
+
+    /// The instruction word for this instruction; the inverse of `decode`.
+    pub fn encode(&self) -> i64 {
+        let mode_digit = |m: Mode| match m {
+            Mode::Position  => 0,
+            Mode::Immediate => 1,
+            Mode::Relative  => 2,
+        };
+
+        self.opcode + (mode_digit(self.modes[0]) * 100)
+                    + (mode_digit(self.modes[1]) * 1000)
+                    + (mode_digit(self.modes[2]) * 10000)
+    }
+
+    /// The standard instruction's name, or `???` for one that's only in
+    /// some dialect.
+    pub fn mnemonic(&self) -> &'static str {
+        InstructionSet::standard().operation(self.opcode).map(|x| x.mnemonic).unwrap_or("???")
+    }
+
+    /// The number of argument cells following the instruction word.
+    pub fn arity(&self) -> usize {
+        InstructionSet::standard().operation(self.opcode).map(|x| x.arity()).unwrap_or(0)
+    }
+
+    /// Whether the last argument is a destination address, rather than a
+    /// value to read.
+    pub fn writes(&self) -> bool {
+        InstructionSet::standard().operation(self.opcode).and_then(|x| x.destination()).is_some()
+    }
+
+    pub fn is_jump(&self) -> bool {
+        self.opcode == JMPIF || self.opcode == JMPNIF
+    }
+
+    /// Whether a jump instruction is taken, given its condition argument.
+    pub fn jumps_on(&self, condition: i64) -> bool {
+        match self.opcode {
+            JMPIF  => condition != 0,
+            JMPNIF => condition == 0,
+            _      => false,
+        }
+    }
+
+    pub fn is_input(&self) -> bool {
+        self.opcode == INPUT
+    }
+
+    pub fn is_halt(&self) -> bool {
+        self.opcode == HALT
+    }
+
+    /// Compute the result of an arithmetic instruction on the given
+    /// arguments, if this is one.
+    pub fn evaluate(&self, arg1: i64, arg2: i64) -> Option<i64> {
+        match self.opcode {
+            ADD      => arg1.checked_add(arg2),
+            MULTIPLY => arg1.checked_mul(arg2),
+            _        => None,
+        }
+    }
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fault::UnknownMode(x)       => write!(f, "Unknown mode value: {}", x),
+            Fault::UnknownOpcode(x)     => write!(f, "Unknown instruction {}", x),
+            Fault::ImmediateDestination => write!(f, "Immediate mode used for a destination"),
+            Fault::NegativeAddress(x)   => write!(f, "Negative address {}", x),
+            Fault::OutOfMemory(x, limit) =>
+                write!(f, "Writing address {} would use more than {} memory cells", x, limit),
+            Fault::Overflow(x, y)       => write!(f, "Arithmetic on {} and {} overflowed", x, y),
+        }
+    }
+}
+
+impl fmt::Display for MachineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MachineError::Io(e)       => write!(f, "Couldn't read program: {}", e),
+            MachineError::Encoding(e) => write!(f, "Program isn't text: {}", e),
+            MachineError::Parse(line, column, s) =>
+                write!(f, "Line {}, column {}: '{}' isn't a number", line, column, s),
+            MachineError::Binary(problem) =>
+                write!(f, "Bad binary program: {}", problem),
+            MachineError::Fault(addr, instruction, fault) =>
+                write!(f, "{} at address {} (instruction {})", fault, addr, instruction),
+            MachineError::Snapshot(line, problem) =>
+                write!(f, "Bad snapshot, line {}: {}", line, problem),
+            MachineError::Unsaveable(problem) =>
+                write!(f, "Can't snapshot this machine: {}", problem),
+            MachineError::Transcript(line, problem) =>
+                write!(f, "Bad transcript, line {}: {}", line, problem),
+        }
+    }
+}
+
+/// The tracers attached to a computer, shared by its clones.
+#[derive(Clone, Default)]
+struct Tracers(Vec<Arc<Mutex<dyn Tracer>>>);
+
+impl PartialEq for Tracers {
+    fn eq(&self, _other: &Tracers) -> bool {
+        true
+    }
+}
+
+impl fmt::Debug for Tracers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} tracer(s)", self.0.len())
+    }
+}
+
+/// Instructions already decoded from the program image, by address.
+#[derive(Clone, Debug, Default)]
+struct DecodeCache(Vec<Option<Instruction>>);
+
+impl PartialEq for DecodeCache {
+    fn eq(&self, _other: &DecodeCache) -> bool {
+        true
+    }
+}
+
+/// The instruction set a computer runs, if it isn't the standard one.
+#[derive(Clone, Debug, Default)]
+struct Dialect(Option<Arc<InstructionSet>>);
+
+impl Dialect {
+    fn set(&self) -> &InstructionSet {
+        match &self.0 {
+            Some(set) => set,
+            None      => InstructionSet::standard(),
+        }
+    }
+}
+
+impl PartialEq for Dialect {
+    /// Sets with the same opcodes, names and arguments are taken to be the
+    /// same.
+    fn eq(&self, other: &Dialect) -> bool {
+        let shape = |x: &Dialect| x.set().operations().map(|(opcode, x)| (opcode, x.mnemonic, x.roles)).collect::<Vec<_>>();
+        std::ptr::eq(self.set(), other.set()) || shape(self) == shape(other)
+    }
+}
+
+/// How many more instructions a computer may run, and until when.
+#[derive(Clone, Debug, Default)]
+struct Limits {
+    steps: Option<u64>,
+    deadline: Option<Instant>,
+}
+
+impl PartialEq for Limits {
+    fn eq(&self, _other: &Limits) -> bool {
+        true
+    }
+}
+
+impl Limits {
+    /// Whether the machine has to stop before its next instruction. Only
+    /// looks at the clock if asked to.
+    fn exhausted(&self, check_clock: bool) -> bool {
+        self.steps == Some(0) || (check_clock && self.deadline.map(|x| Instant::now() >= x).unwrap_or(false))
+    }
+
+    fn spend(&mut self) {
+        if let Some(steps) = self.steps.as_mut() {
+            *steps -= 1;
         }
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+/// What an instruction did, as far as whoever's running the machine cares.
+enum Outcome {
+    Continue,
+    Input,
+    Output(i64),
+    Halted,
+    OutOfBudget,
+}
+
+/// Everything about a machine's state apart from its memory.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Registers {
+    pub position: usize,
+    pub relative_base: i64,
+    pub done: bool,
+    pub awaiting_input: Option<usize>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct Computer {
-    memory: Vec<i64>,
+    memory: Memory,
     position: usize,
     relative_base: i64,
-    done: bool
+    done: bool,
+    awaiting_input: Option<usize>,
+    tracers: Tracers,
+    decoded: DecodeCache,
+    limits: Limits,
+    arithmetic: Arithmetic,
+    dialect: Dialect,
 }
 
 pub enum RunResult {
-    Input(Box<dyn FnOnce(i64) -> Computer>),
+    /// The machine is stopped at an `INPUT`; hand it a value with
+    /// `provide_input` to carry on.
+    Input(Computer),
     Output(i64, Computer),
     Continue(Computer),
     Halted(Computer),
+    /// The machine used up its step budget or its time, and stopped between
+    /// instructions. Give it more with `with_step_budget` or
+    /// `with_time_limit` and run it again to carry on.
+    OutOfBudget(Computer),
+    Error(MachineError),
+}
+
+impl FromStr for Computer {
+    type Err = MachineError;
+
+    fn from_str(char_buffer: &str) -> Result<Computer, MachineError> {
+        let memory = loader::parse_text(char_buffer)?;
+        Ok(Computer{ memory: Memory::from(memory), ..Computer::default() })
+    }
 }
 
 impl Computer {
-    pub fn load(path: &str) -> Computer {
-        let byte_buffer = fs::read(path).unwrap();
-        let char_buffer = str::from_utf8(&byte_buffer).unwrap();
-        Computer::from_string(&char_buffer)
+    /// Load a program from a file, either as text or, if it looks like
+    /// one, as a binary image.
+    pub fn load(path: &str) -> Result<Computer, MachineError> {
+        let byte_buffer = fs::read(path)?;
+        if loader::is_binary(&byte_buffer) {
+            let memory = loader::parse_binary(&byte_buffer)?;
+            return Ok(Computer{ memory: Memory::from(memory), ..Computer::default() });
+        }
+        let char_buffer = str::from_utf8(&byte_buffer)?;
+        Computer::from_str(char_buffer)
+    }
+
+    /// Write out the program image in the given format, ready to `load`.
+    /// Only the memory goes; for the whole machine, take a `Snapshot`.
+    pub fn save(&self, path: &str, format: Format) -> Result<(), MachineError> {
+        match format {
+            Format::Text   => fs::write(path, loader::to_text(self.memory()))?,
+            Format::Binary => fs::write(path, loader::to_binary(self.memory()))?,
+        }
+        Ok(())
+    }
+
+    /// Build a computer from program text we know is good, like the
+    /// examples in tests; use `from_str` for anything else.
+    #[cfg(test)]
+    pub fn from_string(char_buffer: &str) -> Computer {
+        match Computer::from_str(char_buffer) {
+            Ok(computer) => computer,
+            Err(e)       => panic!("{}", e),
+        }
+    }
+
+    /// Rebuild a computer from its saved state, as `Snapshot` does.
+    pub fn from_state(memory: Memory, position: usize, relative_base: i64, done: bool, awaiting_input: Option<usize>) -> Computer {
+        Computer{ memory, position, relative_base, done, awaiting_input, ..Computer::default() }
+    }
+
+    /// Report every instruction this computer (or any clone of it) executes
+    /// to the given tracer, in addition to any it already has.
+    pub fn with_tracer(mut self, tracer: Arc<Mutex<dyn Tracer>>) -> Computer {
+        self.tracers.0.push(tracer);
+        self
     }
 
-    fn from_string(char_buffer: &str) -> Computer {
-        let mut memory = vec![];
-        let mut char_iter = char_buffer.chars().peekable();
+    fn trace(&self, address: usize, instruction: Instruction, arguments: &[i64], effect: Effect) {
+        if self.tracers.0.is_empty() {
+            return;
+        }
 
-        while char_iter.peek().is_some() {
-            let next_iter = char_iter.by_ref().take_while(|x| *x != ',');
-            let next_str = String::from_iter(next_iter);
-            let next = i64::from_str_radix(&next_str.trim(), 10).unwrap();
-            memory.push(next);
+        let operation = self.instruction_set().operation(instruction.opcode);
+        let roles = operation.map(|x| x.roles).unwrap_or(&[]);
+        let mnemonic = operation.map(|x| x.mnemonic).unwrap_or("???");
+        let operands: Vec<i64> = (1..=roles.len()).map(|i| self.memory.get(address + i)).collect();
+        let sources = operands.iter().zip(instruction.modes.iter()).zip(roles.iter()).filter(|(_, role)| **role == Role::Read);
+        let reads = sources.map(|((value, mode), _)| match mode {
+            Mode::Position  => Computer::address(*value).ok(),
+            Mode::Relative  => self.relative(*value).ok(),
+            Mode::Immediate => None,
+        }).collect();
+        let event = Event{ address, instruction, mnemonic, roles, operands, arguments: arguments.to_vec(), reads, effect };
+        for tracer in self.tracers.0.iter() {
+            tracer.lock().expect("tracer poisoned").trace(&event);
         }
+    }
+
+    /// Set the most memory cells this computer may use; past that, writes
+    /// fail with `Fault::OutOfMemory`.
+    pub fn with_memory_limit(mut self, cells: usize) -> Computer {
+        self.memory.set_limit(cells);
+        self
+    }
+
+    pub fn with_arithmetic(mut self, arithmetic: Arithmetic) -> Computer {
+        self.arithmetic = arithmetic;
+        self
+    }
+
+    pub fn arithmetic(&self) -> Arithmetic {
+        self.arithmetic
+    }
+
+    /// Run programs with the given instruction set in place of the standard
+    /// one.
+    pub fn with_instruction_set(mut self, set: Arc<InstructionSet>) -> Computer {
+        self.dialect = Dialect(Some(set));
+        self.decoded = DecodeCache::default();
+        self
+    }
+
+    pub fn instruction_set(&self) -> &InstructionSet {
+        self.dialect.set()
+    }
+
+    /// Let the machine run at most this many more instructions before it
+    /// stops with `RunResult::OutOfBudget`. Stopping at an `INPUT` counts as
+    /// running it.
+    pub fn with_step_budget(mut self, steps: u64) -> Computer {
+        self.limits.steps = Some(steps);
+        self
+    }
+
+    /// Let the machine run for about this much longer, from now, before it
+    /// stops with `RunResult::OutOfBudget`.
+    pub fn with_time_limit(mut self, limit: Duration) -> Computer {
+        self.limits.deadline = Some(Instant::now() + limit);
+        self
+    }
 
-        Computer{ memory, position: 0, relative_base: 0, done: false }
+    /// How many more instructions the machine may run, if it has a budget.
+    pub fn steps_left(&self) -> Option<u64> {
+        self.limits.steps
+    }
+
+    /// What, beyond its memory and registers, makes this machine run
+    /// differently from a plain one, if anything.
+    pub fn customization(&self) -> Option<&'static str> {
+        if self.dialect != Dialect::default() {
+            Some("it runs a dialect")
+        } else if self.arithmetic != Arithmetic::default() {
+            Some("it checks its arithmetic")
+        } else if self.limits.steps.is_some() || self.limits.deadline.is_some() {
+            Some("it has a step budget or time limit")
+        } else {
+            None
+        }
     }
 
     pub fn show(&self) {
-       for (idx, val) in self.memory.iter().enumerate() {
+       for (idx, val) in self.memory.image().iter().enumerate() {
+           println!("{:08}: {}", idx, val);
+       }
+       for (idx, val) in self.memory.sparse_cells() {
            println!("{:08}: {}", idx, val);
        }
        println!("POSITION: {}", self.position);
     }
 
-    pub fn read(&mut self, idx: usize) -> i64 {
-        if idx >= self.memory.len() {
-            self.memory.resize(idx + 1, 0);
+    /// The program image, as currently loaded into memory. Anything the
+    /// program has written past the end of it is in `full_memory`.
+    pub fn memory(&self) -> &[i64] {
+        self.memory.image()
+    }
+
+    pub fn full_memory(&self) -> &Memory {
+        &self.memory
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn relative_base(&self) -> i64 {
+        self.relative_base
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// If the machine is stopped at an `INPUT`, the address the input will
+    /// be written to.
+    pub fn awaiting_input(&self) -> Option<usize> {
+        self.awaiting_input
+    }
+
+    pub fn registers(&self) -> Registers {
+        Registers{ position: self.position, relative_base: self.relative_base, done: self.done, awaiting_input: self.awaiting_input }
+    }
+
+    pub fn set_registers(&mut self, registers: Registers) {
+        self.position = registers.position;
+        self.relative_base = registers.relative_base;
+        self.done = registers.done;
+        self.awaiting_input = registers.awaiting_input;
+    }
+
+    /// The address the next instruction will write to, if it writes
+    /// anywhere and we can tell where.
+    pub fn destination(&self) -> Option<usize> {
+        if self.awaiting_input.is_some() {
+            return self.awaiting_input;
+        }
+
+        let instruction = self.instruction_set().decode(self.read(self.position)).ok()?;
+        let idx = self.instruction_set().operation(instruction.opcode)?.destination()?;
+        self.read_dest(instruction.modes[idx], self.position + idx + 1).ok()
+    }
+
+    /// Give a machine stopped at an `INPUT` its value, and move it past the
+    /// instruction. Panics if the machine isn't waiting for input.
+    pub fn provide_input(mut self, value: i64) -> Computer {
+        self.accept_input(value);
+        self
+    }
+
+    fn accept_input(&mut self, value: i64) {
+        let dest = self.awaiting_input.take().expect("provided input to a machine that didn't want any");
+        let address = self.position;
+        // Look at the instruction before the input lands, in case it lands
+        // on the instruction itself.
+        let instruction = self.instruction_set().decode(self.read(address)).ok();
+        let arity = instruction.and_then(|x| self.instruction_set().operation(x.opcode)).map(|x| x.arity()).unwrap_or(1);
+
+        self.write(dest, value).expect("INPUT destination was reserved");
+        self.position += 1 + arity;
+        if let Some(instruction) = instruction {
+            self.trace(address, instruction, &[], Effect::Write(dest, value));
+        }
+    }
+
+    pub fn read(&self, idx: usize) -> i64 {
+        self.memory.get(idx)
+    }
+
+    fn address(ptr: i64) -> Result<usize, Fault> {
+        if ptr < 0 {
+            return Err(Fault::NegativeAddress(ptr));
         }
-        self.memory[idx]
+        Ok(ptr as usize)
     }
 
-    pub fn read_arg(&mut self, mode: Mode, val: usize) -> i64 {
+    pub fn read_arg(&self, mode: Mode, val: usize) -> Result<i64, Fault> {
         match mode {
             Mode::Position => {
-                let ptr = self.read(val) as usize;
-                self.read(ptr)
+                let ptr = Computer::address(self.read(val))?;
+                Ok(self.read(ptr))
             }
-            Mode::Immediate => self.read(val),
+            Mode::Immediate => Ok(self.read(val)),
             Mode::Relative  => {
-                let ptr = self.read(val) + self.relative_base;
-                self.read(ptr as usize)
+                let ptr = self.relative(self.read(val))?;
+                Ok(self.read(ptr))
             }
         }
     }
 
-    fn read_dest(&mut self, mode: Mode, val: usize) -> usize {
-        assert_ne!(mode, Mode::Immediate);
-        let mut base = self.read(val);
-        if mode == Mode::Relative {
-            base += self.relative_base;
+    fn read_dest(&self, mode: Mode, val: usize) -> Result<usize, Fault> {
+        match mode {
+            Mode::Immediate => Err(Fault::ImmediateDestination),
+            Mode::Relative  => self.relative(self.read(val)),
+            Mode::Position  => Computer::address(self.read(val)),
         }
-        assert!(base >= 0);
-        base as usize
     }
 
-    pub fn write(&mut self, idx: usize, val: i64) {
-        if idx >= self.memory.len() {
-            self.memory.resize(idx + 1, 0);
-        }
-        self.memory[idx] = val;
+    /// The address the given distance from the relative base. If that
+    /// doesn't even fit in a cell, it's nowhere we could ever get to.
+    fn relative(&self, offset: i64) -> Result<usize, Fault> {
+        let ptr = offset.checked_add(self.relative_base).ok_or(Fault::Overflow(offset, self.relative_base))?;
+        Computer::address(ptr)
     }
 
-    fn step(mut self) -> RunResult {
-        let next_instruction = self.read(self.position);
-        let opcode = next_instruction % 100;
-        let arg1mode = Mode::from((next_instruction / 100) % 10);
-        let arg2mode = Mode::from((next_instruction / 1000) % 10);
-        let arg3mode = Mode::from((next_instruction / 10000) % 10);
+    pub fn write(&mut self, idx: usize, val: i64) -> Result<(), Fault> {
+        if let Some(slot) = self.decoded.0.get_mut(idx) {
+            *slot = None;
+        }
+        self.memory.set(idx, val)
+    }
 
-        match opcode {
-            ADD => {
-                let arg1 = self.read_arg(arg1mode, self.position + 1);
-                let arg2 = self.read_arg(arg2mode, self.position + 2);
-                let dest = self.read_dest(arg3mode, self.position + 3) as usize;
+    /// The instruction at the given address, from the cache if we've decoded
+    /// it before.
+    fn decoded(&mut self, address: usize) -> Result<Instruction, Fault> {
+        if let Some(Some(instruction)) = self.decoded.0.get(address) {
+            return Ok(*instruction);
+        }
 
-                self.write(dest, arg1 + arg2);
-                self.position += 4;
-                RunResult::Continue(self)
-            }
-            MULTIPLY => {
-                let arg1 = self.read_arg(arg1mode, self.position + 1);
-                let arg2 = self.read_arg(arg2mode, self.position + 2);
-                let dest = self.read_dest(arg3mode, self.position + 3) as usize;
-
-                self.write(dest, arg1 * arg2);
-                self.position += 4;
-                RunResult::Continue(self)
-            }
-            INPUT => {
-                let dest = self.read_dest(arg1mode, self.position + 1) as usize;
-                self.position += 2;
-                RunResult::Input(Box::new(move |x| {
-                    self.write(dest, x);
-                    self
-                }))
+        let instruction = self.instruction_set().decode(self.read(address))?;
+        let image = self.memory.image().len();
+        if address < image {
+            if self.decoded.0.len() < image {
+                self.decoded.0.resize(image, None);
             }
-            OUTPUT => {
-                let arg1 = self.read_arg(arg1mode, self.position + 1);
-                self.position += 2;
-                RunResult::Output(arg1, self)
+            self.decoded.0[address] = Some(instruction);
+        }
+        Ok(instruction)
+    }
+
+    /// Execute the instruction at the current position. A faulting
+    /// instruction comes back as `RunResult::Error`, rather than a panic.
+    /// This decodes the instruction afresh every time; `run` and friends are
+    /// the quicker way to get through a lot of instructions.
+    pub fn step(mut self) -> RunResult {
+        if self.awaiting_input.is_some() {
+            return RunResult::Input(self);
+        }
+
+        if self.limits.exhausted(true) {
+            return RunResult::OutOfBudget(self);
+        }
+
+        let address = self.position;
+        let next_instruction = self.read(address);
+
+        match self.instruction_set().decode(next_instruction).and_then(|x| self.execute(x)) {
+            Ok(outcome) => {
+                self.limits.spend();
+                self.finish(outcome)
             }
-            JMPIF => {
-                let arg1 = self.read_arg(arg1mode, self.position + 1);
-                let arg2 = self.read_arg(arg2mode, self.position + 2);
+            Err(fault)  => RunResult::Error(MachineError::Fault(address, next_instruction, fault)),
+        }
+    }
 
-                if arg1 != 0 {
-                    self.position = arg2 as usize;
-                } else {
-                    self.position += 3;
+    fn finish(self, outcome: Outcome) -> RunResult {
+        match outcome {
+            Outcome::Continue  => RunResult::Continue(self),
+            Outcome::Input     => RunResult::Input(self),
+            Outcome::Output(x) => RunResult::Output(x, self),
+            Outcome::Halted    => RunResult::Halted(self),
+            Outcome::OutOfBudget => RunResult::OutOfBudget(self),
+        }
+    }
+
+    /// Execute an instruction, as its operation in our instruction set says
+    /// to: read its arguments, see what it makes of them, and do that.
+    fn execute(&mut self, instruction: Instruction) -> Result<Outcome, Fault> {
+        let operation = *self.instruction_set().operation(instruction.opcode).ok_or(Fault::UnknownOpcode(instruction.opcode))?;
+        let address = self.position;
+        let mut arguments = [0; 3];
+        let mut reads = 0;
+        let mut dest = None;
+
+        for (idx, role) in operation.roles.iter().enumerate() {
+            let mode = instruction.modes[idx];
+            match role {
+                Role::Read => {
+                    arguments[reads] = self.read_arg(mode, address + idx + 1)?;
+                    reads += 1;
                 }
-                RunResult::Continue(self)
+                Role::Write => dest = Some(self.read_dest(mode, address + idx + 1)?),
             }
-            JMPNIF => {
-                let arg1 = self.read_arg(arg1mode, self.position + 1);
-                let arg2 = self.read_arg(arg2mode, self.position + 2);
+        }
 
-                if arg1 == 0 {
-                    self.position = arg2 as usize;
-                } else {
-                    self.position += 3;
-                }
-                RunResult::Continue(self)
+        let arguments = &arguments[..reads];
+        let next = address + operation.arity() + 1;
+        let dest = || dest.unwrap_or_else(|| panic!("{} has nowhere to write to", operation.mnemonic));
+
+        match (operation.semantics)(self, arguments)? {
+            Action::Continue => {
+                self.position = next;
+                self.trace(address, instruction, arguments, Effect::Nothing);
+                Ok(Outcome::Continue)
             }
-            LESS_THAN => {
-                let arg1 = self.read_arg(arg1mode, self.position + 1);
-                let arg2 = self.read_arg(arg2mode, self.position + 2);
-                let dest = self.read_dest(arg3mode, self.position + 3) as usize;
-
-                self.write(dest, if arg1 < arg2 { 1 } else { 0 });
-                self.position += 4;
-                RunResult::Continue(self)
+            Action::Write(result) => {
+                let dest = dest();
+                self.write(dest, result)?;
+                self.position = next;
+                self.trace(address, instruction, arguments, Effect::Write(dest, result));
+                Ok(Outcome::Continue)
             }
-            EQUALS => {
-                let arg1 = self.read_arg(arg1mode, self.position + 1);
-                let arg2 = self.read_arg(arg2mode, self.position + 2);
-                let dest = self.read_dest(arg3mode,self.position + 3) as usize;
-
-                self.write(dest, if arg1 == arg2 { 1 } else { 0 });
-                self.position += 4;
-                RunResult::Continue(self)
+            Action::Output(value) => {
+                self.position = next;
+                self.trace(address, instruction, arguments, Effect::Output(value));
+                Ok(Outcome::Output(value))
             }
-            ADJUST_BASE => {
-                let arg1 = self.read_arg(arg1mode, self.position + 1);
-
-                self.relative_base += arg1;
-                self.position += 2;
-                RunResult::Continue(self)
+            Action::Jump(target) => {
+                self.position = match target {
+                    Some(target) => Computer::address(target)?,
+                    None         => next,
+                };
+                self.trace(address, instruction, arguments, Effect::Jump(self.position));
+                Ok(Outcome::Continue)
             }
-            HALT => {
-                self.done = true;
-                RunResult::Halted(self)
+            Action::Base(base) => {
+                // Trace before moving the base, so the tracers can work out
+                // where the argument came from.
+                self.trace(address, instruction, arguments, Effect::Base(base));
+                self.relative_base = base;
+                self.position = next;
+                Ok(Outcome::Continue)
             }
-            /* */
-            unknown_pos =>
-              panic!("Unknown instruction {}", unknown_pos)
-        }
-    }
-
-    pub fn run(mut self) -> RunResult {
-        loop {
-            match self.step() {
-                RunResult::Continue(next) =>
-                    self = next,
-                result =>
-                    return result,
+            Action::Input => {
+                let dest = dest();
+                // Providing the input can't fail, so make sure now that the
+                // write will work. We stay on the instruction until then.
+                self.memory.reserve(dest)?;
+                self.awaiting_input = Some(dest);
+                Ok(Outcome::Input)
+            }
+            Action::Halt => {
+                self.done = true;
+                self.trace(address, instruction, arguments, Effect::Halt);
+                Ok(Outcome::Halted)
             }
         }
     }
 
-    pub fn standard_run(mut self, inputs: &[i64]) -> Vec<i64> {
-        let mut idx = 0;
-        let mut res = vec![];
+    /// Execute instructions in place until one needs something from outside
+    /// the machine, looking them up in the decode cache as we go.
+    fn run_in_place(&mut self) -> Result<Outcome, MachineError> {
+        let limited = self.limits.steps.is_some() || self.limits.deadline.is_some();
+        let mut until_clock = 0;
 
         loop {
-            match self.step() {
-                RunResult::Continue(next) =>
-                    self = next,
-                RunResult::Halted(_) =>
-                    return res,
-                RunResult::Input(c) if idx < inputs.len() => {
-                    self = c(inputs[idx]);
-                    idx += 1;
-                }
-                RunResult::Input(_) =>
-                    panic!("Ran out of inputs in standard run."),
-                RunResult::Output(x, next) => {
-                    res.push(x);
-                    self = next;
+            if limited {
+                if self.limits.exhausted(until_clock == 0) {
+                    return Ok(Outcome::OutOfBudget);
                 }
+                until_clock = if until_clock == 0 { CLOCK_INTERVAL } else { until_clock - 1 };
             }
-        }
-    }
 
-    pub fn serialize(&self, inputs: Vec<i64>) -> i64 {
-        let mut previous_output = VecDeque::new();
-
-        previous_output.push_back(0);
-        for input in inputs.iter() {
-            let mut my_machine = self.clone().prime(*input);
-            let mut output = VecDeque::new();
-
-            loop {
-                match my_machine.run() {
-                    RunResult::Continue(next) =>
-                        my_machine = next,
-                    RunResult::Halted(_) =>
-                        break,
-                    RunResult::Input(c) =>
-                        match previous_output.pop_front() {
-                            None =>
-                                panic!("Serialized machine wanted input I didn't have!"),
-                            Some(next_input) =>
-                                my_machine = c(next_input),
-                        },
-                    RunResult::Output(o, next) => {
-                        output.push_back(o);
-                        my_machine = next;
-                    }
-                }
+            let address = self.position;
+            let outcome = self.decoded(address).and_then(|x| self.execute(x));
+            if limited && outcome.is_ok() {
+                self.limits.spend();
             }
-            previous_output = output;
-        }
-
-        assert_eq!(previous_output.len(), 1);
-        previous_output[0]
-    }
-
-    pub fn find_best_signal<F>(&self, range: Range<i64>, f: F) -> (i64, Vec<i64>)
-      where F: Fn(Vec<i64>) -> i64
-    {
-        let mut best_score = 0;
-        let mut best_result = vec![];
-
-        for a in range.clone() {
-            for b in range.clone() {
-                if b == a { continue; }
-                for c in range.clone() {
-                    if c == a || c == b { continue; }
-                    for d in range.clone() {
-                        if d == c || d == b || d == a { continue; }
-                        for e in range.clone() {
-                            if e == d || e == c || e == b || e == a { continue; }
-                            let inputs = vec![a,b,c,d,e];
-                            let result = f(inputs);
-                            if result > best_score {
-                                best_score = result;
-                                best_result = vec![a,b,c,d,e];
-                            }
-                        }
-                    }
-                }
+            match outcome {
+                Ok(Outcome::Continue) => {}
+                Ok(outcome)           => return Ok(outcome),
+                Err(fault)            => return Err(MachineError::Fault(address, self.read(address), fault)),
             }
         }
+    }
+
+    pub fn run(mut self) -> RunResult {
+        if self.awaiting_input.is_some() {
+            return RunResult::Input(self);
+        }
 
-        (best_score, best_result)
+        match self.run_in_place() {
+            Ok(outcome) => self.finish(outcome),
+            Err(e)      => RunResult::Error(e),
+        }
     }
 
-    pub fn prime(self, input: i64) -> Self {
-        match self.run() {
-            RunResult::Input(c) => c(input),
-            _                   =>
-                panic!("Priming failure: machine didn't ask for input first.")
+    /// Keep going with the given engine, `run` or `step`, calling out to the
+    /// device whenever the machine reads or writes.
+    fn drive<D: Device>(mut self, device: &mut D, engine: fn(Computer) -> RunResult) -> RunResult {
+        loop {
+            self = match engine(self) {
+                RunResult::Continue(next)  => next,
+                RunResult::Input(next)     => match device.next_input() {
+                    None    => return RunResult::Input(next),
+                    Some(x) => next.provide_input(x),
+                },
+                RunResult::Output(x, next) => if device.on_output(x) == Flow::Stop {
+                    return RunResult::Output(x, next);
+                } else {
+                    next
+                },
+                result                     => return result,
+            };
         }
     }
 
-    pub fn amplifier(&self, settings: Vec<i64>) -> i64 {
-        assert_eq!(settings.len(), 5);
+    /// Run the machine with the given device attached, until it halts or
+    /// fails, or the device says to stop. If the device ran out of input,
+    /// this comes back as `RunResult::Input`; if it stopped the machine after
+    /// an output, as `RunResult::Output`.
+    pub fn run_with<D: Device>(self, device: &mut D) -> RunResult {
+        self.drive(device, Computer::run)
+    }
 
-        let mut machine_a = self.clone().prime(settings[0]);
-        let mut machine_b = self.clone().prime(settings[1]);
-        let mut machine_c = self.clone().prime(settings[2]);
-        let mut machine_d = self.clone().prime(settings[3]);
-        let mut machine_e = self.clone().prime(settings[4]);
-        let mut last_output = 0;
+    /// `run_with`, but one `step` at a time, decoding every instruction
+    /// afresh. It gets the same answers, just slower, so it's what we check
+    /// and measure the fast path against.
+    pub fn step_with<D: Device>(self, device: &mut D) -> RunResult {
+        self.drive(device, Computer::step)
+    }
 
-        loop {
-            let aout = loop { match machine_a.run() {
-                RunResult::Halted(_) => return last_output,
-                RunResult::Output(o, next) => { machine_a = next; break o; }
-                RunResult::Input(c) => machine_a = c(last_output),
-                _ => panic!("Unexpted aout"),
-            } };
-            let bout = loop { match machine_b.run() {
-                RunResult::Halted(_) => return last_output,
-                RunResult::Output(o, next) => { machine_b = next; break o; }
-                RunResult::Input(c) => machine_b = c(aout),
-                _ => panic!("Unexpted aout"),
-            } };
-            let cout = loop { match machine_c.run() {
-                RunResult::Halted(_) => return last_output,
-                RunResult::Output(o, next) => { machine_c = next; break o; }
-                RunResult::Input(c) => machine_c = c(bout),
-                _ => panic!("Unexpted aout"),
-            } };
-            let dout = loop { match machine_d.run() {
-                RunResult::Halted(_) => return last_output,
-                RunResult::Output(o, next) => { machine_d = next; break o; }
-                RunResult::Input(c) => machine_d = c(cout),
-                _ => panic!("Unexpted aout"),
-            } };
-            let eout = loop { match machine_e.run() {
-                RunResult::Halted(_) => return last_output,
-                RunResult::Output(o, next) => { machine_e = next; break o; }
-                RunResult::Input(c) => machine_e = c(dout),
-                _ => panic!("Unexpted aout"),
-            } };
-            last_output = eout;
+    /// The outputs of a run that had the given inputs, which should have
+    /// halted. Panics if it didn't.
+    fn outputs_of(result: RunResult, feeder: Feeder) -> Vec<i64> {
+        match result {
+            RunResult::Halted(_)      => feeder.outputs,
+            RunResult::Input(_)       => panic!("Ran out of inputs in standard run."),
+            RunResult::OutOfBudget(_) => panic!("Ran out of budget in standard run."),
+            RunResult::Error(e)       => panic!("Error in standard run: {}", e),
+            RunResult::Output(..) | RunResult::Continue(_) => unreachable!("the feeder never stops the machine"),
         }
     }
+
+    /// Run the machine on the given inputs until it halts, and return what it
+    /// wrote out.
+    pub fn standard_run(self, inputs: &[i64]) -> Vec<i64> {
+        let mut feeder = Feeder::new(inputs);
+        let result = self.run_with(&mut feeder);
+        Computer::outputs_of(result, feeder)
+    }
+
+    /// `standard_run`, done with `step_with`.
+    pub fn stepped_run(self, inputs: &[i64]) -> Vec<i64> {
+        let mut feeder = Feeder::new(inputs);
+        let result = self.step_with(&mut feeder);
+        Computer::outputs_of(result, feeder)
+    }
 }
 
 #[cfg(test)]
 fn run_example(computer: Vec<i64>, inputs: &[i64], targets: &[i64]) {
-    let day5a = Computer{ memory: computer, position: 0, relative_base: 0, done: false };
+    let day5a = Computer{ memory: computer.into(), position: 0, relative_base: 0, done: false, ..Computer::default() };
     run_computer(day5a, inputs, targets);
 }
 
@@ -377,49 +882,52 @@ fn run_computer(computer: Computer, inputs: &[i64], targets: &[i64]) {
 
 #[test]
 fn test_examples() {
+    use crate::search::Search;
+    use crate::topology::amplify;
+
     let example1 = Computer::from_string("1,0,0,0,99");
-    let answer1  = Computer{ memory: vec![2,0,0,0,99], position: 4, relative_base: 0, done: false };
+    let answer1  = Computer{ memory: vec![2,0,0,0,99].into(), position: 4, relative_base: 0, done: false, ..Computer::default() };
     match example1.step() {
         RunResult::Continue(result) => assert_eq!(answer1, result),
         _                           => assert!(false),
     }
 
     let example2 = Computer::from_string("2,3,0,3,99");
-    let answer2 = Computer{ memory: vec![2,3,0,6,99], position: 4, relative_base: 0, done: false };
+    let answer2 = Computer{ memory: vec![2,3,0,6,99].into(), position: 4, relative_base: 0, done: false, ..Computer::default() };
     match example2.step() {
         RunResult::Continue(result) => assert_eq!(answer2, result),
         _                           => assert!(false),
     }
 
     let example3 = Computer::from_string("2,4,4,5,99,0");
-    let answer3 = Computer{ memory: vec![2,4,4,5,99,9801], position: 4, relative_base: 0, done: false };
+    let answer3 = Computer{ memory: vec![2,4,4,5,99,9801].into(), position: 4, relative_base: 0, done: false, ..Computer::default() };
     match example3.step() {
         RunResult::Continue(result) => assert_eq!(answer3, result),
         _                           => assert!(false),
     }
 
     let example4 = Computer::from_string("1,1,1,4,99,5,6,0,99");
-    let answer4 = Computer{ memory: vec![30,1,1,4,2,5,6,0,99], position: 8, relative_base: 0, done: true };
+    let answer4 = Computer{ memory: vec![30,1,1,4,2,5,6,0,99].into(), position: 8, relative_base: 0, done: true, ..Computer::default() };
     match example4.run() {
         RunResult::Halted(result) => assert_eq!(answer4, result),
         _                         => assert!(false),
     }
 
     let example5 = Computer::from_string("1002,4,3,4,33");
-    let answer5 = Computer{ memory: vec![1002,4,3,4,99], position: 4, relative_base: 0, done: true };
+    let answer5 = Computer{ memory: vec![1002,4,3,4,99].into(), position: 4, relative_base: 0, done: true, ..Computer::default() };
     match example5.run() {
         RunResult::Halted(result) => assert_eq!(answer5, result),
         _                         => assert!(false),
     }
 
     let example6 = Computer::from_string("1101,100,-1,4,0");
-    let answer6 = Computer{ memory: vec![1101,100,-1,4,99], position: 4, relative_base: 0, done: true };
+    let answer6 = Computer{ memory: vec![1101,100,-1,4,99].into(), position: 4, relative_base: 0, done: true, ..Computer::default() };
     match example6.run() {
         RunResult::Halted(result) => assert_eq!(answer6, result),
         _                         => assert!(false),
     }
 
-    let day5a = Computer::load("inputs/day5");
+    let day5a = Computer::load("inputs/day5").unwrap();
     let target = vec![0,0,0,0,0,0,0,0,0,7_259_358];
     let outputs = day5a.standard_run(&[1]);
     assert_eq!(target, outputs);
@@ -448,32 +956,32 @@ fn test_examples() {
                 &[192], &[1001]);
 
     let example7a = Computer::from_string("3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0");
-    let result7a = example7a.serialize(vec![4,3,2,1,0]);
+    let result7a = amplify(&example7a, &[4,3,2,1,0], false).unwrap();
     assert_eq!(43210, result7a);
     let example7b = Computer::from_string("3,23,3,24,1002,24,10,24,1002,23,-1,23,101,5,23,23,1,24,23,23,4,23,99,0,0");
-    let result7b = example7b.serialize(vec![0,1,2,3,4]);
+    let result7b = amplify(&example7b, &[0,1,2,3,4], false).unwrap();
     assert_eq!(54321, result7b);
     let example7c = Computer::from_string("3,31,3,32,1002,32,10,32,1001,31,-2,31,1007,31,0,33,1002,33,7,33,1,33,31,31,1,32,31,31,4,31,99,0,0,0");
     let target7c = 65210;
-    let result7c = example7c.serialize(vec![1,0,4,3,2]);
+    let result7c = amplify(&example7c, &[1,0,4,3,2], false).unwrap();
     assert_eq!(target7c, result7c);
-    let result7c2 = example7c.serialize(vec![1,0,4,3,2]);
+    let result7c2 = amplify(&example7c, &[1,0,4,3,2], false).unwrap();
     assert_eq!(target7c, result7c2);
     assert_eq!(result7c2, 65210);
-    assert_eq!(example7c.find_best_signal(0..5, |x| example7c.serialize(x)).1, vec![1,0,4,3,2]);
-    let day7a = Computer::load("inputs/day7");
-    let (day7score, day7settings) = day7a.find_best_signal(0..5, |x| day7a.serialize(x));
+    assert_eq!(Search::new(&[0,1,2,3,4]).best(|x| amplify(&example7c, x, false).unwrap()).unwrap().1, vec![1,0,4,3,2]);
+    let day7a = Computer::load("inputs/day7").unwrap();
+    let (day7score, day7settings) = Search::new(&[0,1,2,3,4]).threads(4).best(|x| amplify(&day7a, x, false).unwrap()).unwrap();
     assert_eq!(day7score, 206580);
     assert_eq!(day7settings, vec![2,0,1,4,3]);
 
     let example7e = Computer::from_string("3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5");
-    assert_eq!(139629729, example7e.amplifier(vec![9,8,7,6,5]));
-    let (example7es, example7et) = example7e.find_best_signal(5..10, |x| example7e.amplifier(x));
+    assert_eq!(139629729, amplify(&example7e, &[9,8,7,6,5], true).unwrap());
+    let (example7es, example7et) = Search::new(&[5,6,7,8,9]).best(|x| amplify(&example7e, x, true).unwrap()).unwrap();
     assert_eq!(139629729, example7es);
     assert_eq!(vec![9,8,7,6,5], example7et);
     let example7f = Computer::from_string("3,52,1001,52,-5,52,3,53,1,52,56,54,1007,54,5,55,1005,55,26,1001,54,-5,54,1105,1,12,1,53,54,53,1008,54,0,55,1001,55,1,55,2,53,55,53,4,53,1001,56,-1,56,1005,56,6,99,0,0,0,0,10");
-    assert_eq!(18216, example7f.amplifier(vec![9,7,8,5,6]));
-    let (example7fs, example7ft) = example7f.find_best_signal(5..10, |x| example7f.amplifier(x));
+    assert_eq!(18216, amplify(&example7f, &[9,7,8,5,6], true).unwrap());
+    let (example7fs, example7ft) = Search::new(&[5,6,7,8,9]).best(|x| amplify(&example7f, x, true).unwrap()).unwrap();
     assert_eq!(18216, example7fs);
     assert_eq!(vec![9,7,8,5,6], example7ft);
 
@@ -487,6 +995,153 @@ fn test_examples() {
                 &[],
                 &[1125899906842624]);
 
-    run_computer(Computer::load("inputs/day9"), &[1], &[3063082071]);
-    run_computer(Computer::load("inputs/day9"), &[2], &[81348]);
+    run_computer(Computer::load("inputs/day9").unwrap(), &[1], &[3063082071]);
+    run_computer(Computer::load("inputs/day9").unwrap(), &[2], &[81348]);
+}
+
+#[test]
+fn test_errors() {
+    let faults = [("1,0,0,0,77",    4, 77,    Fault::UnknownOpcode(77)),
+                  ("1,0,0,0,399",   4, 399,   Fault::UnknownMode(3)),
+                  ("11101,1,1,5,99", 0, 11101, Fault::ImmediateDestination),
+                  ("1,-3,0,0,99",   0, 1,     Fault::NegativeAddress(-3)),
+                  ("109,-5,1201,0,0,0,99", 2, 1201, Fault::NegativeAddress(-5)),
+                  ("1105,1,-1",     0, 1105,  Fault::NegativeAddress(-1)),
+                  ("109,9223372036854775807,1201,1,0,0,99", 2, 1201, Fault::Overflow(1, i64::MAX))];
+
+    for (program, address, instruction, fault) in faults.iter() {
+        match Computer::from_string(program).run() {
+            RunResult::Error(MachineError::Fault(a, i, f)) => {
+                assert_eq!((a, i, f), (*address, *instruction, *fault));
+            }
+            _ => panic!("{} didn't fault", program),
+        }
+    }
+
+    // a far-off write only costs a page, until we hit the limit
+    let far = Computer::from_string("21101,3,4,1000000000,204,1000000000,99");
+    assert_eq!(far.clone().standard_run(&[]), vec![7]);
+    match far.with_memory_limit(100).run() {
+        RunResult::Error(MachineError::Fault(0, 21101, Fault::OutOfMemory(1000000000, 100))) => {}
+        _ => panic!("went past the memory limit"),
+    }
+    match Computer::from_string("3,1000000000,99").with_memory_limit(100).run() {
+        RunResult::Error(MachineError::Fault(0, 3, Fault::OutOfMemory(1000000000, 100))) => {}
+        _ => panic!("went past the memory limit on input"),
+    }
+
+    match Computer::from_str("1,2,three,4") {
+        Err(MachineError::Parse(1, 5, ref s)) if s == "three" => {}
+        _ => panic!("bad program parsed"),
+    }
+    match Computer::load("inputs/no_such_day") {
+        Err(MachineError::Io(_)) => {}
+        _ => panic!("loaded a missing file"),
+    }
+}
+
+#[test]
+fn test_input_pause() {
+    let paused = match Computer::from_string("3,9,8,9,10,9,4,9,99,-1,8").run() {
+        RunResult::Input(next) => next,
+        _                      => panic!("equals8 didn't ask for input"),
+    };
+    assert_eq!(paused.position(), 0);
+    assert_eq!(paused.awaiting_input(), Some(9));
+
+    // stepping a paused machine leaves it paused
+    let paused = match paused.step() {
+        RunResult::Input(next) => next,
+        _                      => panic!("paused machine ran without input"),
+    };
+
+    // and we can fork it to try different inputs
+    let fork = paused.clone();
+    assert_eq!(fork, paused);
+    assert_eq!(paused.provide_input(8).standard_run(&[]), vec![1]);
+    let fork = fork.provide_input(7);
+    assert_eq!(fork.awaiting_input(), None);
+    assert_eq!(fork.position(), 2);
+    assert_eq!(fork.standard_run(&[]), vec![0]);
+}
+
+#[test]
+fn test_run_with() {
+    use std::collections::VecDeque;
+
+    struct Echo {
+        inputs: VecDeque<i64>,
+        outputs: Vec<i64>,
+        stop_at: usize,
+    }
+
+    impl Device for Echo {
+        fn next_input(&mut self) -> Option<i64> {
+            self.inputs.pop_front()
+        }
+
+        fn on_output(&mut self, value: i64) -> Flow {
+            self.outputs.push(value);
+            if self.outputs.len() == self.stop_at { Flow::Stop } else { Flow::Continue }
+        }
+    }
+
+    // read a number, print it, and go around again
+    let echo = Computer::from_string("3,7,4,7,1105,1,0,0");
+    let mut device = Echo{ inputs: VecDeque::from(vec![1, 2, 3]), outputs: vec![], stop_at: 2 };
+    let echo = match echo.run_with(&mut device) {
+        RunResult::Output(2, next) => next,
+        _                          => panic!("device didn't stop the machine"),
+    };
+    assert_eq!(device.outputs, vec![1, 2]);
+    match echo.run_with(&mut device) {
+        RunResult::Input(next) => assert_eq!(next.awaiting_input(), Some(7)),
+        _                      => panic!("machine didn't wait for input"),
+    }
+    assert_eq!(device.outputs, vec![1, 2, 3]);
+
+    match Computer::from_string("104,1,99").run_with(&mut device) {
+        RunResult::Halted(next) => assert!(next.is_done()),
+        _                       => panic!("machine didn't halt"),
+    }
+}
+
+#[test]
+fn test_budgets() {
+    // a machine that spins forever gets stopped, and can be sent on again
+    let spinner = Computer::from_string("1105,1,0").with_step_budget(10);
+    let spinner = match spinner.run() {
+        RunResult::OutOfBudget(next) => next,
+        _                            => panic!("spinning machine wasn't stopped"),
+    };
+    assert_eq!(spinner.steps_left(), Some(0));
+    assert_eq!(spinner.position(), 0);
+    assert!(matches!(spinner.clone().step(), RunResult::OutOfBudget(_)));
+    assert!(matches!(spinner.with_step_budget(5).run(), RunResult::OutOfBudget(x) if x.steps_left() == Some(0)));
+
+    // the budget runs out between instructions, so resuming picks up right
+    // where we left off
+    let adder = Computer::from_string("1101,1,1,9,1101,2,2,10,99,0,0").with_step_budget(1);
+    let adder = match adder.run() {
+        RunResult::OutOfBudget(next) => next,
+        _                            => panic!("adder ran past its budget"),
+    };
+    assert_eq!((adder.position(), adder.read(9), adder.read(10)), (4, 2, 0));
+    match adder.with_step_budget(2).run() {
+        RunResult::Halted(next) => assert_eq!((next.read(10), next.steps_left()), (4, Some(0))),
+        _                       => panic!("adder didn't finish"),
+    }
+
+    // stopping at an INPUT uses up a step, so there's none left to go on with
+    let waiting = Computer::from_string("3,9,8,9,10,9,4,9,99,-1,8").with_step_budget(1);
+    let waiting = match waiting.run() {
+        RunResult::Input(next) => next,
+        _                      => panic!("machine didn't wait for input"),
+    };
+    assert_eq!(waiting.steps_left(), Some(0));
+    assert!(matches!(waiting.provide_input(8).run(), RunResult::OutOfBudget(x) if x.position() == 2));
+
+    // a time limit stops it too, if not straight away
+    let spinner = Computer::from_string("1105,1,0").with_time_limit(Duration::from_millis(10));
+    assert!(matches!(spinner.run(), RunResult::OutOfBudget(_)));
 }
@@ -1,20 +1,35 @@
 mod arcade;
 mod args;
+mod ascii;
+mod assembler;
+mod bench;
+mod bignum;
 #[cfg(test)]
 mod bugs;
 #[cfg(test)]
 mod cards;
+mod cfg;
 #[cfg(test)]
 mod chemistry;
+mod coverage;
+mod debugger;
+mod device;
+mod disasm;
 #[cfg(test)]
 mod donut;
+mod endchannel;
 #[cfg(test)]
 mod fft;
 mod fuel;
+mod fuzz;
+mod history;
 mod image;
+mod isa;
+mod loader;
 mod machine;
 #[cfg(test)]
 mod maze;
+mod memory;
 #[cfg(test)]
 mod nbody;
 mod orbits;
@@ -27,22 +42,72 @@ mod router;
 mod santafind;
 #[cfg(test)]
 mod scaffold;
+mod search;
+mod snapshot;
+mod spawn;
 #[cfg(test)]
 mod spring;
 #[cfg(test)]
 mod station;
+mod symbolic;
+mod topology;
+mod trace;
+mod transcript;
 #[cfg(test)]
 mod tractor;
+mod watch;
 mod wiremap;
 
 use crate::args::Command;
+use crate::ascii::Ascii;
+use crate::assembler::assemble;
+use crate::bench::{time, workloads};
+use crate::cfg::control_flow;
+use crate::coverage::Coverage;
+use crate::debugger::Debugger;
+use crate::device::{Device, Flow};
+use crate::disasm::disassemble;
 use crate::fuel::calculate_fuel;
+use crate::fuzz::{fast, fuzz, stepped};
+use crate::machine::RunResult;
 use crate::orbits::Object;
 use crate::santafind::find_santa;
+use crate::search::Search;
+use crate::topology::amplify;
+use crate::trace::{Profiler, TraceLog};
+use crate::transcript::{replay, Recorder};
 use crate::wiremap::WireMap;
 use std::cmp::{max,min};
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
 use terminal_graphics::Display;
 
+/// What the compute command's machine reads from and writes to: a fixed
+/// list of inputs, and the screen.
+struct Console {
+    inputs: std::vec::IntoIter<i64>,
+}
+
+impl Device for Console {
+    fn next_input(&mut self) -> Option<i64> {
+        self.inputs.next()
+    }
+
+    fn on_output(&mut self, value: i64) -> Flow {
+        println!("Received value: {}", value);
+        Flow::Continue
+    }
+}
+
+/// Save the transcript the given recorder made, if we were asked for one.
+fn save_transcript(recorder: &Arc<Mutex<Recorder>>, path: Option<String>) {
+    if let Some(path) = path {
+        if let Err(e) = recorder.lock().unwrap().transcript().save(&path) {
+            println!("Couldn't save transcript: {}", e);
+        }
+    }
+}
+
 fn main() {
     match Command::get() {
         Command::ComputeFuel(masses) => {
@@ -57,13 +122,56 @@ fn main() {
             println!("TOTAL FUEL: {}", total);
         }
 
-        Command::RunComputer(initial) => {
+        Command::RunComputer(mut initial, trace, profile, coverage, record) => {
             println!("Initial Computer:");
             initial.show();
+            let program = initial.clone();
+            let log = Arc::new(Mutex::new(TraceLog::new(io::stdout())));
+            if trace {
+                initial = initial.with_tracer(log.clone());
+            }
+            let profiler = Arc::new(Mutex::new(Profiler::new()));
+            if profile {
+                initial = initial.with_tracer(profiler.clone());
+            }
+            let covered = Arc::new(Mutex::new(Coverage::new()));
+            if coverage {
+                initial = initial.with_tracer(covered.clone());
+            }
+            let recorder = Arc::new(Mutex::new(Recorder::new()));
+            if record.is_some() {
+                initial = initial.with_tracer(recorder.clone());
+            }
+            println!("Running, with input 5.");
+            let stopped = match initial.run_with(&mut Console{ inputs: vec![5].into_iter() }) {
+                RunResult::Input(next)       => { println!("Computer wants more input than we have."); Some(next) }
+                RunResult::Halted(next)      => Some(next),
+                RunResult::OutOfBudget(next) => { println!("Out of budget; stopped at address {}.", next.position()); None }
+                RunResult::Error(e)          => { println!("ERROR: {}", e); None }
+                RunResult::Output(..) | RunResult::Continue(_) => unreachable!("the console never stops the machine"),
+            };
+            if let Some(steps) = stopped.and_then(|x| x.steps_left()) {
+                println!("Steps left in budget: {}", steps);
+            }
+            let _ = log.lock().unwrap().sink().flush();
+            if profile {
+                print!("{}", profiler.lock().unwrap());
+            }
+            if coverage {
+                print!("{}", covered.lock().unwrap().report(&program));
+            }
+            save_transcript(&recorder, record);
+        }
+
+        Command::RunBig(initial) => {
             println!("Running, with input 5.");
-            let results = initial.standard_run(&[5]);
-            for val in results.iter() {
-                println!("Received value: {}", val);
+            match initial.standard_run(&[5.into()]) {
+                Err(e) => println!("ERROR: {}", e),
+                Ok(results) => {
+                    for val in results.iter() {
+                        println!("Received value: {}", val);
+                    }
+                }
             }
         }
 
@@ -156,11 +264,14 @@ fn main() {
             }
         }
 
-        Command::Amplify(computer) => {
-            let (amount_a, settings_a) = computer.find_best_signal(0..5, |x| computer.serialize(x));
-            println!("Best signal without loopback is {} @ {:?}", amount_a, settings_a);
-            let (amount_b, settings_b) = computer.find_best_signal(5..10, |x| computer.amplifier(x));
-            println!("Best signal with loopback is {} @ {:?}", amount_b, settings_b);
+        Command::Amplify(computer, goal, keep, threads, length) => {
+            let search = |candidates: &[i64]| Search::new(candidates).length(length).goal(goal).top(keep).threads(threads);
+            for (amount, settings) in search(&[0,1,2,3,4]).run(|x| amplify(&computer, x, false).expect("no amplifiers to run")) {
+                println!("Best signal without loopback is {} @ {:?}", amount, settings);
+            }
+            for (amount, settings) in search(&[5,6,7,8,9]).run(|x| amplify(&computer, x, true).expect("no amplifiers to run")) {
+                println!("Best signal with loopback is {} @ {:?}", amount, settings);
+            }
         }
 
         Command::Image(image) => {
@@ -184,8 +295,12 @@ fn main() {
             image.draw();
         }
 
-        Command::Arcade(arcade) => {
+        Command::Arcade(mut arcade, record) => {
             let mut screen = Display::new(40, 40);
+            let recorder = Arc::new(Mutex::new(Recorder::new()));
+            if record.is_some() {
+                arcade = arcade.with_tracer(recorder.clone());
+            }
 
             screen.clear();
             let result = arcade.run(move |a| {
@@ -193,10 +308,107 @@ fn main() {
                 screen.print();
             });
             println!("Final score: {}", result.score);
+            save_transcript(&recorder, record);
+        }
+
+        Command::FindSanta(comp, checkpoint) => {
+            find_santa(comp, checkpoint.as_deref());
+        }
+
+        Command::Disassemble(comp, entries) => {
+            print!("{}", disassemble(&comp, &entries));
+        }
+
+        Command::Benchmark(dir, rounds) => {
+            match workloads(&dir) {
+                Err(e) => println!("ERROR: {}", e),
+                Ok(workloads) => {
+                    for workload in workloads.iter() {
+                        println!("{}", time(workload, rounds));
+                    }
+                }
+            }
+        }
+
+        Command::Fuzz(seed, count) => {
+            match fuzz(seed, count, stepped, fast) {
+                Ok(())       => println!("All {} programs ran the same on both engines.", count),
+                Err(failure) => println!("{}", failure),
+            }
+        }
+
+        Command::Convert(comp, path, format) => {
+            if let Err(e) = comp.save(&path, format) {
+                println!("ERROR: {}", e);
+            }
+        }
+
+        Command::Network(mut network, names, threaded) => {
+            let result = if threaded { network.run_threaded() } else { network.run() };
+            for name in names.iter() {
+                let state = match network.computer(name) {
+                    Some(x) if x.is_done()                  => "halted",
+                    Some(x) if x.awaiting_input().is_some() => "waiting for input",
+                    _                                       => "stopped",
+                };
+                println!("{} ({}): {:?}", name, state, network.outputs(name).unwrap_or(&[]));
+            }
+            if let Err(e) = result {
+                println!("ERROR: {}", e);
+            }
+        }
+
+        Command::Symbolic(explorer, at) => {
+            let exploration = explorer.explore();
+            print!("{}", exploration);
+            if !at.is_empty() {
+                match exploration.path_for(&at) {
+                    Some(path) => {
+                        let outputs: Vec<i64> = path.outputs.iter().map(|x| x.evaluate(&at)).collect();
+                        println!("Inputs {:?} take this path, and output {:?}:\n{}", at, outputs, path);
+                    }
+                    None =>
+                        println!("Inputs {:?} don't take any path found.", at),
+                }
+            }
+        }
+
+        Command::ControlFlow(comp, entries, dot) => {
+            let graph = control_flow(&comp, &entries);
+            if dot {
+                print!("{}", graph.to_dot());
+            } else {
+                print!("{}", graph);
+            }
+        }
+
+        Command::Assemble(source) => {
+            match assemble(&source) {
+                Err(e) =>
+                    println!("ERROR: {}", e),
+                Ok(program) =>
+                    println!("{}", program),
+            }
+        }
+
+        Command::Ascii(mut comp, record) => {
+            let recorder = Arc::new(Mutex::new(Recorder::new()));
+            if record.is_some() {
+                comp = comp.with_tracer(recorder.clone());
+            }
+            Ascii::new(comp).interact();
+            save_transcript(&recorder, record);
+        }
+
+        Command::Replay(comp, transcript) => {
+            match replay(comp, &transcript) {
+                Ok(_)  => println!("Replayed {} inputs and outputs; everything matched.", transcript.entries.len()),
+                Err(e) => println!("Replay diverged. {}", e),
+            }
         }
 
-        Command::FindSanta(comp) => {
-            find_santa(comp);
+        Command::Debug(comp) => {
+            Debugger::new(comp).interact();
         }
     }
  }
\ No newline at end of file
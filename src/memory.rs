@@ -0,0 +1,189 @@
+use crate::machine::Fault;
+use std::collections::{BTreeMap, HashMap};
+
+/// The number of cells in each page of sparse memory.
+const PAGE_SIZE: usize = 1024;
+
+/// How many cells a computer may use, unless told otherwise: 128MB worth.
+pub const DEFAULT_MEMORY_LIMIT: usize = 1 << 24;
+
+/// A computer's memory. The program image lives in a plain vector, since
+/// that's where nearly all the work happens, and it grows to cover writes
+/// just past its end. Writes further out go into pages allocated on demand,
+/// so a program poking at some huge address only costs us a page. Unwritten
+/// cells read as zero, and reading never allocates anything.
+#[derive(Clone, Debug)]
+pub struct Memory {
+    image: Vec<i64>,
+    pages: HashMap<usize, Box<[i64]>>,
+    limit: usize,
+}
+
+impl Memory {
+    /// Set the most cells this memory will ever hold, counting the program
+    /// image. Writes that would need more fail with `Fault::OutOfMemory`.
+    pub fn set_limit(&mut self, cells: usize) {
+        self.limit = cells;
+    }
+
+    /// The program image (plus whatever it's grown to cover), without any of
+    /// the sparse pages.
+    pub fn image(&self) -> &[i64] {
+        &self.image
+    }
+
+    /// The number of cells we've allocated space for.
+    pub fn allocated(&self) -> usize {
+        self.image.len() + self.pages.len() * PAGE_SIZE
+    }
+
+    pub fn get(&self, addr: usize) -> i64 {
+        if addr < self.image.len() {
+            return self.image[addr];
+        }
+
+        match self.pages.get(&(addr / PAGE_SIZE)) {
+            None       => 0,
+            Some(page) => page[addr % PAGE_SIZE],
+        }
+    }
+
+    /// Make sure there's room for the given address, so that a later `set`
+    /// to it can't fail.
+    pub fn reserve(&mut self, addr: usize) -> Result<(), Fault> {
+        if addr < self.image.len() || self.pages.contains_key(&(addr / PAGE_SIZE)) {
+            return Ok(());
+        }
+
+        if addr < self.image.len() + PAGE_SIZE {
+            return self.grow(addr + 1);
+        }
+
+        if self.allocated() + PAGE_SIZE > self.limit {
+            return Err(Fault::OutOfMemory(addr, self.limit));
+        }
+        self.pages.insert(addr / PAGE_SIZE, vec![0; PAGE_SIZE].into_boxed_slice());
+        Ok(())
+    }
+
+    /// Extend the image to at least the given length, taking over anything
+    /// already written to pages it now covers. A page it only partly covers
+    /// gets taken over whole, so no cell is ever allocated twice.
+    fn grow(&mut self, length: usize) -> Result<(), Fault> {
+        let start = self.image.len();
+        let covered: Vec<usize> = self.pages.keys().cloned().filter(|x| x * PAGE_SIZE < length).collect();
+        let end = covered.iter().map(|x| (x + 1) * PAGE_SIZE).fold(length, usize::max);
+
+        if end + (self.pages.len() - covered.len()) * PAGE_SIZE > self.limit {
+            return Err(Fault::OutOfMemory(length - 1, self.limit));
+        }
+
+        let tail: Vec<i64> = (start..end).map(|addr| self.get(addr)).collect();
+        self.image.extend(tail);
+        for page_number in covered.iter() {
+            self.pages.remove(page_number);
+        }
+        Ok(())
+    }
+
+    pub fn set(&mut self, addr: usize, value: i64) -> Result<(), Fault> {
+        if addr < self.image.len() {
+            self.image[addr] = value;
+            return Ok(());
+        }
+
+        self.reserve(addr)?;
+        if addr < self.image.len() {
+            self.image[addr] = value;
+        } else {
+            let page = self.pages.get_mut(&(addr / PAGE_SIZE)).expect("reserved page vanished");
+            page[addr % PAGE_SIZE] = value;
+        }
+        Ok(())
+    }
+
+    /// All the non-zero cells outside the program image, in address order.
+    pub fn sparse_cells(&self) -> BTreeMap<usize, i64> {
+        let mut result = BTreeMap::new();
+
+        for (page_number, page) in self.pages.iter() {
+            for (offset, value) in page.iter().enumerate() {
+                let addr = page_number * PAGE_SIZE + offset;
+                if *value != 0 && addr >= self.image.len() {
+                    result.insert(addr, *value);
+                }
+            }
+        }
+
+        result
+    }
+}
+
+impl Default for Memory {
+    fn default() -> Memory {
+        Memory::from(vec![])
+    }
+}
+
+impl From<Vec<i64>> for Memory {
+    fn from(image: Vec<i64>) -> Memory {
+        Memory{ image, pages: HashMap::new(), limit: DEFAULT_MEMORY_LIMIT }
+    }
+}
+
+/// Two memories are the same if they'd read the same everywhere, however
+/// they got there.
+impl PartialEq for Memory {
+    fn eq(&self, other: &Memory) -> bool {
+        let length = self.image.len().max(other.image.len());
+
+        (0..length).all(|addr| self.get(addr) == other.get(addr)) &&
+            self.sparse_cells().range(length..).eq(other.sparse_cells().range(length..))
+    }
+}
+
+#[test]
+fn sparse_memory() {
+    let mut memory = Memory::from(vec![1, 2, 3]);
+    assert_eq!(memory.get(1), 2);
+    assert_eq!(memory.get(1_000_000_000), 0);
+    assert_eq!(memory.allocated(), 3);
+
+    memory.set(1_000_000_000, 7).unwrap();
+    assert_eq!(memory.get(1_000_000_000), 7);
+    assert_eq!(memory.get(1_000_000_001), 0);
+    assert_eq!(memory.allocated(), 3 + PAGE_SIZE);
+    assert_eq!(memory.sparse_cells().into_iter().collect::<Vec<_>>(), vec![(1_000_000_000, 7)]);
+
+    // writing just past the image grows it, unless there's already a page there
+    let mut other = Memory::from(vec![]);
+    other.set(1025, 4).unwrap();
+    assert_eq!(other.image(), &[]);
+    other.set(5, 6).unwrap();
+    assert_eq!(other.image(), &[0, 0, 0, 0, 0, 6]);
+    other.set(1020, 8).unwrap();
+    other.set(1030, 9).unwrap();
+    assert_eq!(other.image().len(), 1021);
+    assert_eq!(other.get(1025), 4);
+    assert_eq!(other.allocated(), 1021 + PAGE_SIZE);
+    assert_eq!(other.sparse_cells().into_iter().collect::<Vec<_>>(), vec![(1025, 4), (1030, 9)]);
+
+    // growing over part of a page takes the whole page into the image
+    other.grow(1500).unwrap();
+    assert_eq!(other.image().len(), 2 * PAGE_SIZE);
+    assert_eq!(other.allocated(), 2 * PAGE_SIZE);
+    assert_eq!(other.get(1030), 9);
+
+    let mut other = Memory::from(vec![1, 2, 3]);
+    other.set(5, 0).unwrap();
+    assert_eq!(other.image().len(), 6);
+    assert_ne!(memory, other);
+    other.set(1_000_000_000, 7).unwrap();
+    assert_eq!(memory, other);
+
+    memory.set_limit(3 + PAGE_SIZE);
+    memory.set(1_000_000_010, 8).unwrap();
+    assert_eq!(memory.set(2_000_000_000, 9), Err(Fault::OutOfMemory(2_000_000_000, 3 + PAGE_SIZE)));
+    assert_eq!(memory.set(10, 9), Err(Fault::OutOfMemory(10, 3 + PAGE_SIZE)));
+    assert_eq!(memory.get(10), 0);
+}
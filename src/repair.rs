@@ -1,5 +1,7 @@
+use crate::device::{Device, Flow};
 use crate::machine::{Computer, RunResult};
 use std::collections::VecDeque;
+use std::slice;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 enum Direction {
@@ -107,39 +109,55 @@ impl MoveResult {
     }
 }
 
+/// Walks the droid along a path, until it runs out of path or into a wall.
+struct PathWalker<'a> {
+    steps: slice::Iter<'a, Direction>,
+    last_response: MoveResult,
+}
+
+impl<'a> Device for PathWalker<'a> {
+    fn next_input(&mut self) -> Option<i64> {
+        self.steps.next().map(|x| x.encode())
+    }
+
+    fn on_output(&mut self, x: i64) -> Flow {
+        self.last_response = MoveResult::new(x);
+        if self.last_response == MoveResult::HitWall {
+            Flow::Stop
+        } else {
+            Flow::Continue
+        }
+    }
+}
+
+/// Makes a single move, stopping as soon as we hear back how it went.
+struct SingleMove {
+    direction: Option<Direction>,
+}
+
+impl Device for SingleMove {
+    fn next_input(&mut self) -> Option<i64> {
+        self.direction.take().map(|x| x.encode())
+    }
+
+    fn on_output(&mut self, _: i64) -> Flow {
+        Flow::Stop
+    }
+}
+
 impl RepairSearch {
     fn new(f: &str) -> RepairSearch {
-        let computer = Computer::load(f);
+        let computer = Computer::load(f).expect("Couldn't load Intcode program");
         RepairSearch { computer }
     }
 
     fn try_path(&mut self, path: &Path) -> MoveResult {
-        let mut my_computer = self.computer.clone();
-        let mut last_response = MoveResult::Done;
-        let mut idx = 0;
+        let mut walker = PathWalker{ steps: path.steps.iter(), last_response: MoveResult::Done };
 
-        loop {
-            match my_computer.run() {
-                RunResult::Continue(next) =>
-                    my_computer = next,
-                RunResult::Halted(_) =>
-                    return last_response,
-                RunResult::Output(x, next) => {
-                    my_computer = next;
-                    last_response = MoveResult::new(x);
-                    if last_response == MoveResult::HitWall {
-                        return last_response;
-                    }
-                }
-                RunResult::Input(c)  => {
-                    if idx >= path.steps.len() {
-                        return last_response;
-                    }
-                    my_computer = c(path.steps[idx].encode());
-                    idx += 1;
-                }
-            }
+        if let RunResult::Error(e) = self.computer.clone().run_with(&mut walker) {
+            panic!("Repair droid failed: {}", e);
         }
+        walker.last_response
     }
 
     fn run_search(&mut self) -> usize {
@@ -183,7 +201,7 @@ struct Room {
 
 impl Room {
     fn new(width: usize, height: usize, f: &str) -> Room {
-        let computer = Computer::load(f);
+        let computer = Computer::load(f).expect("Couldn't load Intcode program");
         let mut layout = Vec::with_capacity(width * height);
 
         layout.resize(width * height, Tile::Unknown);
@@ -269,39 +287,37 @@ impl Room {
             return (self, false);
         }
 
-        loop {
-            match self.computer.run() {
-                RunResult::Continue(next) =>
-                    self.computer = next,
-                RunResult::Halted(next) => {
-                    self.computer = next;
-                    return (self, false);
-                }
-                RunResult::Input(c) =>
-                    self.computer = c(direction.encode()),
-                RunResult::Output(resp, next) => {
-                    let response = MoveResult::new(resp);
-
-                    self.computer = next;
-                    match response {
-                        MoveResult::HitWall => {
-                            self.set(tx, ty, Tile::Wall);
-                            return (self, false);
-                        }
-                        MoveResult::Done => {
-                            self.set(tx, ty, Tile::Empty);
-                            self.x = tx;
-                            self.y = ty;
-                            return (self, true);
-                        }
-                        MoveResult::FoundSystem => {
-                            self.set(tx, ty, Tile::Oxygen);
-                            self.x = tx;
-                            self.y = ty;
-                            return (self, true);
-                        }
-                    }
-                }
+        let response = match self.computer.run_with(&mut SingleMove{ direction: Some(direction) }) {
+            RunResult::Error(e) =>
+                panic!("Repair droid failed: {}", e),
+            RunResult::Output(resp, next) => {
+                self.computer = next;
+                MoveResult::new(resp)
+            }
+            RunResult::Halted(next) => {
+                self.computer = next;
+                return (self, false);
+            }
+            _ =>
+                panic!("Repair droid wanted another move before answering"),
+        };
+
+        match response {
+            MoveResult::HitWall => {
+                self.set(tx, ty, Tile::Wall);
+                (self, false)
+            }
+            MoveResult::Done => {
+                self.set(tx, ty, Tile::Empty);
+                self.x = tx;
+                self.y = ty;
+                (self, true)
+            }
+            MoveResult::FoundSystem => {
+                self.set(tx, ty, Tile::Oxygen);
+                self.x = tx;
+                self.y = ty;
+                (self, true)
             }
         }
     }
@@ -1,3 +1,4 @@
+use crate::device::{Device, Flow};
 use crate::machine::{Computer, RunResult};
 use image::{ImageBuffer, Rgb};
 
@@ -9,9 +10,31 @@ struct HullGrid {
     robot_dir: Direction,
 }
 
+/// Tells the robot what color it's standing on, and waits to hear what
+/// color to paint and which way to turn.
+struct Camera {
+    color: i64,
+    outputs: Vec<i64>,
+}
+
+impl Device for Camera {
+    fn next_input(&mut self) -> Option<i64> {
+        Some(self.color)
+    }
+
+    fn on_output(&mut self, value: i64) -> Flow {
+        self.outputs.push(value);
+        if self.outputs.len() == 2 {
+            Flow::Stop
+        } else {
+            Flow::Continue
+        }
+    }
+}
+
 impl HullGrid {
     fn new(width: u32, height: u32, computer_path: &str) -> HullGrid {
-        let computer = Computer::load(computer_path);
+        let computer = Computer::load(computer_path).expect("Couldn't load Intcode program");
 
         assert!(width & 1 == 1);
         assert!(height & 1 == 1);
@@ -47,46 +70,38 @@ impl HullGrid {
     }
 
     fn paint_next(mut self, output: Option<&str>) -> Option<Self> {
-        let mut new_color: Option<i64> = None;
-
-        loop {
-            let color = if self.is_white() { 1 } else { 0 };
-
-            match self.computer.run() {
-                RunResult::Continue(next) =>
-                    self.computer = next,
-                RunResult::Halted(next) => {
-                    self.computer = next;
-                    if let Some(fname) = output {
-                        self.render(fname);
-                    }
-                    return None;
-                }
-                RunResult::Input(c) =>
-                    self.computer = c(color),
-                RunResult::Output(o, next) if new_color.is_none() => {
-                    new_color = Some(o);
-                    self.computer = next;
+        let mut camera = Camera{ color: if self.is_white() { 1 } else { 0 }, outputs: vec![] };
+
+        match self.computer.run_with(&mut camera) {
+            RunResult::Error(e) =>
+                panic!("Painting robot failed: {}", e),
+            RunResult::Halted(next) => {
+                self.computer = next;
+                if let Some(fname) = output {
+                    self.render(fname);
                 }
-                RunResult::Output(rotation, next) => {
-                    self.computer = next;
-
-                    if new_color.unwrap() == 0 {
-                        self.set_black()
-                    } else {
-                        self.set_white()
-                    }
-
-                    self.robot_dir = if rotation == 0 {
-                        self.robot_dir.rotate_right()
-                    } else {
-                        self.robot_dir.rotate_left()
-                    };
-
-                    self.step();
-                    return Some(self);
+                None
+            }
+            RunResult::Output(rotation, next) => {
+                self.computer = next;
+
+                if camera.outputs[0] == 0 {
+                    self.set_black()
+                } else {
+                    self.set_white()
                 }
+
+                self.robot_dir = if rotation == 0 {
+                    self.robot_dir.rotate_right()
+                } else {
+                    self.robot_dir.rotate_left()
+                };
+
+                self.step();
+                Some(self)
             }
+            _ =>
+                unreachable!("the camera always has a color to give"),
         }
     }
 
@@ -1,64 +1,68 @@
+use crate::device::{Device, Flow};
 use crate::machine::{Computer, RunResult};
 use itertools::Itertools;
 use std::collections::{HashMap, VecDeque};
 use std::ops::Range;
 
 struct ComputerState {
-    next: Box<dyn FnOnce(i64) -> Computer>,
+    next: Computer,
     input_queue: VecDeque<i64>,
 }
 
+/// A computer's network card: it reads packets off the computer's queue,
+/// and writes whatever the computer sends to a shared outbox.
+struct Nic<'a> {
+    input_queue: &'a mut VecDeque<i64>,
+    output: &'a mut VecDeque<i64>,
+}
+
+impl<'a> Device for Nic<'a> {
+    fn next_input(&mut self) -> Option<i64> {
+        self.input_queue.pop_front()
+    }
+
+    fn on_output(&mut self, value: i64) -> Flow {
+        self.output.push_back(value);
+        Flow::Continue
+    }
+}
+
 impl ComputerState {
-    fn new(mut c: Computer, address: i64) -> ComputerState {
-        let mut sent_address = false;
-
-        loop {
-            match c.run() {
-                RunResult::Continue(next) =>
-                    c = next,
-                RunResult::Halted(_) =>
-                    panic!("Computer halted right away!"),
-                RunResult::Output(_, _) =>
-                    panic!("Computer sent output right away!"),
-                RunResult::Input(f) if sent_address =>
-                    return ComputerState {
-                        next: f,
-                        input_queue: VecDeque::new(),
-                    },
-                RunResult::Input(f) => {
-                    c = f(address);
-                    sent_address = true;
-                }
-            }
+    fn new(c: Computer, address: i64) -> ComputerState {
+        let mut input_queue = VecDeque::new();
+        let mut output = VecDeque::new();
+
+        input_queue.push_back(address);
+        match c.run_with(&mut Nic{ input_queue: &mut input_queue, output: &mut output }) {
+            RunResult::Error(e) =>
+                panic!("Network computer failed: {}", e),
+            RunResult::Input(next) if output.is_empty() =>
+                ComputerState {
+                    next,
+                    input_queue,
+                },
+            RunResult::Input(_) =>
+                panic!("Computer sent output right away!"),
+            _ =>
+                panic!("Computer halted right away!"),
         }
     }
 
     fn run(mut self, output: &mut VecDeque<i64>) -> Option<Self> {
-        let mut c = match self.input_queue.pop_front() {
-            None => (self.next)(-1),
-            Some(x) => (self.next)(x),
+        let c = match self.input_queue.pop_front() {
+            None => self.next.provide_input(-1),
+            Some(x) => self.next.provide_input(x),
         };
 
-        loop {
-            match c.run() {
-                RunResult::Continue(next) =>
-                    c = next,
-                RunResult::Halted(_) =>
-                    return None,
-                RunResult::Output(o, next) => {
-                    output.push_back(o);
-                    c = next;
-                } 
-                RunResult::Input(f) =>
-                    match self.input_queue.pop_front() {
-                        None => {
-                            self.next = f;
-                            return Some(self);
-                        }
-                        Some(x) =>
-                            c = f(x),
-                    }
+        match c.run_with(&mut Nic{ input_queue: &mut self.input_queue, output }) {
+            RunResult::Error(e) =>
+                panic!("Network computer failed: {}", e),
+            RunResult::Input(next) => {
+                self.next = next;
+                Some(self)
             }
+            _ =>
+                None,
         }
     }
 }
@@ -73,7 +77,7 @@ struct Router {
 impl Router {
     fn new(file: &str, nodes: Range<usize>) -> Router {
         let mut map = HashMap::new();
-        let c = Computer::load(file);
+        let c = Computer::load(file).expect("Couldn't load Intcode program");
 
         for i in nodes {
             map.insert(i, ComputerState::new(c.clone(), i as i64));
@@ -1,4 +1,7 @@
-use crate::machine::{Computer, RunResult};
+use crate::ascii::Ascii;
+use crate::machine::Computer;
+use crate::snapshot::Snapshot;
+use std::path::Path;
 
 const GATHER_STEPS: [&'static str; 34] = [
     "south",
@@ -48,72 +51,65 @@ const THINGS: [&'static str; 8] = [
     "monolith",
 ];
 
-fn combine_commands(cmds: &[&str]) -> String {
-    let mut res = String::new();
-
-    for cmd in cmds.iter() {
-        res.push_str(cmd);
-        res.push_str("\n");
-    }
-
-    res
-}
-
-fn select_things(c: u16) -> String {
-    let mut res = String::new();
+fn select_things(c: u16) -> Vec<String> {
+    let mut res = vec![];
 
     for bit in 0..8 {
         if (c >> bit) & 0x1 == 1 {
-            res.push_str("take ");
-            res.push_str(THINGS[bit]);
-            res.push_str("\n");
+            res.push(format!("take {}", THINGS[bit]));
         }
     }
 
     res
 }
 
-fn run_computer(mut comp: Computer, buffer: &mut String) -> (Box<dyn FnOnce(i64) -> Computer>, String) {
-    let mut outbuf = String::new();
-
-    loop {
-        match comp.run() {
-            RunResult::Continue(comp2) => comp = comp2,
-            RunResult::Halted(_) => panic!("Machine halted in run_computer: {}", outbuf),
-            RunResult::Output(c, comp2) => {
-                outbuf.push(c as u8 as char);
-                comp = comp2;
-            }
-            RunResult::Input(f) => {
-                if buffer.len() == 0 {
-                    return (f, outbuf);
-                }
+/// Type the given commands at the droid, and collect what it says back.
+fn run_commands<S: AsRef<str>>(comp: Computer, commands: &[S]) -> (Ascii, String) {
+    let mut ascii = Ascii::new(comp);
 
-                let c = buffer.remove(0);
-                comp = f(c as u8 as i64);
-            } 
-        }
+    for command in commands.iter() {
+        ascii.send_line(command.as_ref());
+    }
+    if let Err(e) = ascii.run() {
+        panic!("Machine failed in run_computer: {}\n{}", e, ascii.take_text());
     }
+    let outbuf = ascii.take_text();
+    (ascii, outbuf)
 }
 
 fn gather_everything(comp: Computer) -> Computer {
-    let mut gather_buffer = combine_commands(&GATHER_STEPS);
-    let (res, outb) = run_computer(comp, &mut gather_buffer);
+    let (res, outb) = run_commands(comp, &GATHER_STEPS);
     println!("{}", outb);
-    res('\n' as u8 as i64)
+    res.into_computer()
 }
 
 fn combination_works(comp: Computer, code: u16) -> bool {
-    let mut get_buffer = select_things(code);
-    let (next, _) = run_computer(comp, &mut get_buffer);
-    let mut north = "nv\nnorth\n".to_string();
-    let (_after, outbuf) = run_computer(next('i' as u8 as i64), &mut north);
+    let mut commands = select_things(code);
+    commands.push("inv".to_string());
+    commands.push("north".to_string());
+    let (_after, outbuf) = run_commands(comp, &commands);
     println!("outbuf: {}", outbuf);
     !outbuf.contains("heavier") && !outbuf.contains("lighter")
 }
 
-pub fn find_santa(base_computer: Computer) {
-    let at_checkpoint = gather_everything(base_computer);
+/// Reaching the checkpoint takes a while, so if given a snapshot file we
+/// start from there instead, saving it first if it doesn't exist yet.
+fn reach_checkpoint(base_computer: Computer, snapshot: Option<&str>) -> Computer {
+    match snapshot {
+        None =>
+            gather_everything(base_computer),
+        Some(path) if Path::new(path).exists() =>
+            Snapshot::load(path).expect("Couldn't load checkpoint").computer,
+        Some(path) => {
+            let at_checkpoint = gather_everything(base_computer);
+            Snapshot::new(at_checkpoint.clone(), &[]).save(path).expect("Couldn't save checkpoint");
+            at_checkpoint
+        }
+    }
+}
+
+pub fn find_santa(base_computer: Computer, snapshot: Option<&str>) {
+    let at_checkpoint = reach_checkpoint(base_computer, snapshot);
 
     for code in 1..256 {
         println!("------------------------------------------");
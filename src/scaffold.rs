@@ -1,4 +1,5 @@
-use crate::machine::{Computer, RunResult};
+use crate::ascii::{Ascii, AsciiStatus};
+use crate::machine::Computer;
 
 struct ScaffoldMap {
     data: Vec<Tile>,
@@ -8,46 +9,32 @@ struct ScaffoldMap {
 
 impl ScaffoldMap {
     fn new(intcode: &str) -> ScaffoldMap {
-        let mut computer = Computer::load(intcode);
+        let computer = Computer::load(intcode).expect("Couldn't load Intcode program");
+        let mut camera = Ascii::new(computer);
+
+        match camera.run() {
+            Ok(AsciiStatus::Halted) => {}
+            Ok(AsciiStatus::NeedInput) =>
+                panic!("Don't know how to deal with input!"),
+            Ok(AsciiStatus::OutOfBudget) =>
+                panic!("Scaffold program ran out of budget"),
+            Err(e) =>
+                panic!("Scaffold program failed: {}", e),
+        }
+
         let mut data = Vec::new();
         let mut width = 0;
         let mut height = 0;
-        let mut got_width = false;
-
-        loop {
-            match computer.run() {
-                RunResult::Halted(_) => {
-                    height -= 1;
-                    assert_eq!(height, (data.len() / width));
-                    return ScaffoldMap{
-                        data,
-                        width, height
-                    }
-                }
-
-                RunResult::Continue(next) =>
-                    computer = next,
-
-                RunResult::Input(_) =>
-                    panic!("Don't know how to deal with input!"),
 
-                RunResult::Output(o, next) => {
-                    let c = o as u8 as char;
-
-                    computer = next;
-
-                    if c == '\n' {
-                        height += 1;
-                        got_width = true;
-                        continue;
-                    }
-                    if !got_width {
-                        width += 1;
-                    }
-
-                    data.push(Tile::from(c));
-                }
-            }
+        for line in camera.take_text().lines().filter(|x| !x.is_empty()) {
+            width = line.len();
+            height += 1;
+            data.extend(line.chars().map(Tile::from));
+        }
+        assert_eq!(height, (data.len() / width));
+        ScaffoldMap{
+            data,
+            width, height
         }
     }
 
@@ -305,46 +292,35 @@ struct Answer {
 }
 
 trait ToInput {
-    fn to_input(&self) -> Vec<i64>;
+    fn to_input(&self) -> String;
 }
 
 #[derive(Debug)]
 enum Trigger{ A, B, C }
 
 impl ToInput for Trigger {
-    fn to_input(&self) -> Vec<i64> {
+    fn to_input(&self) -> String {
         match self {
-            Trigger::A => vec![65],
-            Trigger::B => vec![66],
-            Trigger::C => vec![67],
+            Trigger::A => "A".to_string(),
+            Trigger::B => "B".to_string(),
+            Trigger::C => "C".to_string(),
         }
     }
 }
 
 impl ToInput for Move {
-    fn to_input(&self) -> Vec<i64> {
+    fn to_input(&self) -> String {
         match self {
-            Move::Forward(x) => x.to_string().chars().map(|x| x as u8 as i64).collect(),
-            Move::Left       => vec![76],
-            Move::Right      => vec![82],
+            Move::Forward(x) => x.to_string(),
+            Move::Left       => "L".to_string(),
+            Move::Right      => "R".to_string(),
         }
     }
 }
 
-fn to_inputs<T: ToInput>(v: &[T]) -> Vec<i64> {
-    let mut res = vec![];
-    let mut viter = v.iter().peekable();
-
-    while let Some(x) = viter.next() {
-        res.extend(x.to_input());
-        if viter.peek().is_some() {
-            res.push(44);
-        } else {
-            res.push(10);
-        }
-    }
-
-    res
+fn to_inputs<T: ToInput>(v: &[T]) -> String {
+    let parts: Vec<String> = v.iter().map(|x| x.to_input()).collect();
+    parts.join(",")
 }
 
 impl Answer {
@@ -362,21 +338,21 @@ impl Answer {
         res
     }
 
-    fn to_inputs(&self) -> Vec<i64> {
+    fn to_inputs(&self) -> Vec<String> {
         let mut res = vec![];
 
         println!("main: {:?}", self.main);
         println!("main': {:?}", to_inputs(&self.main));
-        res.extend(to_inputs(&self.main));
+        res.push(to_inputs(&self.main));
         println!("a: {:?}", self.a);
         println!("a': {:?}", to_inputs(&self.a.moves));
-        res.extend(to_inputs(&self.a.moves));
+        res.push(to_inputs(&self.a.moves));
         println!("b: {:?}", self.b);
         println!("b': {:?}", to_inputs(&self.b.moves));
-        res.extend(to_inputs(&self.b.moves));
+        res.push(to_inputs(&self.b.moves));
         println!("c: {:?}", self.c);
         println!("c': {:?}", to_inputs(&self.c.moves));
-        res.extend(to_inputs(&self.c.moves));
+        res.push(to_inputs(&self.c.moves));
 
         res
     }
@@ -436,21 +412,18 @@ fn day17b() {
     for (a, b, c) in triples {
         for answer in answers(&path, &a, &b, &c).iter() {
             assert_eq!(answer.to_path(), path);
-            let mut comp = Computer::load("inputs/day17");
-            let mut inputs = answer.to_inputs();
+            let mut comp = Computer::load("inputs/day17").unwrap();
 
             assert_eq!(comp.read(0), 1);
-            comp.write(0, 2);
-            inputs.push('n' as u8 as i64);
-            inputs.push('\n' as u8 as i64);
-            let results = comp.standard_run(&inputs);
-            for x in results.iter() {
-                if *x < 256 {
-                    print!("{}", *x as u8 as char);
-                } else {
-                    assert_eq!(768115, *x);
-                }
+            comp.write(0, 2).unwrap();
+            let mut robot = Ascii::new(comp);
+            for line in answer.to_inputs().iter() {
+                robot.send_line(line);
             }
+            robot.send_line("n");
+            assert_eq!(robot.run().unwrap(), AsciiStatus::Halted);
+            print!("{}", robot.take_text());
+            assert_eq!(robot.take_values(), vec![768115]);
         }
     }
 }
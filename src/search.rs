@@ -0,0 +1,202 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Goal {
+    Maximize,
+    Minimize,
+}
+
+/// The orderings of `length` distinct picks from a list of candidates, in
+/// lexicographic order of where the picks sit in that list.
+struct Permutations<'a> {
+    candidates: &'a [i64],
+    picks: Vec<usize>,
+    /// How many of the picks, from the front, stay put.
+    fixed: usize,
+    started: bool,
+    done: bool,
+}
+
+impl<'a> Permutations<'a> {
+    #[cfg(test)]
+    fn new(candidates: &'a [i64], length: usize) -> Permutations<'a> {
+        Permutations::starting_with(candidates, length, &[])
+    }
+
+    /// Just the orderings whose first picks are the given ones.
+    fn starting_with(candidates: &'a [i64], length: usize, prefix: &[usize]) -> Permutations<'a> {
+        let mut picks = prefix.to_vec();
+        while picks.len() < length {
+            let next = (0..).find(|x| !picks.contains(x)).unwrap();
+            picks.push(next);
+        }
+        Permutations{ candidates, picks, fixed: prefix.len(), started: false, done: length > candidates.len() }
+    }
+
+    /// Move the picks on to the next ordering, returning false once we're out.
+    fn advance(&mut self) -> bool {
+        let n = self.candidates.len();
+
+        for idx in (self.fixed..self.picks.len()).rev() {
+            let used = &self.picks[..idx];
+            if let Some(next) = (self.picks[idx] + 1..n).find(|x| !used.contains(x)) {
+                self.picks[idx] = next;
+                for fill in idx + 1..self.picks.len() {
+                    let used = &self.picks[..fill];
+                    self.picks[fill] = (0..n).find(|x| !used.contains(x)).unwrap();
+                }
+                return true;
+            }
+        }
+
+        false
+    }
+}
+
+impl Iterator for Permutations<'_> {
+    type Item = Vec<i64>;
+
+    fn next(&mut self) -> Option<Vec<i64>> {
+        if self.done || (self.started && !self.advance()) {
+            self.done = true;
+            return None;
+        }
+        self.started = true;
+        Some(self.picks.iter().map(|x| self.candidates[*x]).collect())
+    }
+}
+
+/// A search over every ordering of some candidate values for the settings
+/// that score best, like the phase settings for a row of amplifiers. Ties go
+/// to whichever settings come first.
+pub struct Search {
+    candidates: Vec<i64>,
+    length: usize,
+    goal: Goal,
+    threads: usize,
+    keep: usize,
+}
+
+impl Search {
+    /// Search every ordering of all of the given candidates.
+    pub fn new(candidates: &[i64]) -> Search {
+        Search{ candidates: candidates.to_vec(), length: candidates.len(), goal: Goal::Maximize, threads: 1, keep: 1 }
+    }
+
+    /// Only pick this many of the candidates for each setting.
+    pub fn length(mut self, length: usize) -> Search {
+        self.length = length;
+        self
+    }
+
+    pub fn goal(mut self, goal: Goal) -> Search {
+        self.goal = goal;
+        self
+    }
+
+    /// Split the search across this many threads.
+    pub fn threads(mut self, threads: usize) -> Search {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// Keep this many of the best settings, rather than just the one.
+    pub fn top(mut self, keep: usize) -> Search {
+        self.keep = keep;
+        self
+    }
+
+    /// Score every setting, and return the best ones with their scores, best
+    /// first. The settings are split up by their first pick, and each thread
+    /// takes the next first pick nobody has started on yet.
+    pub fn run<F>(&self, score: F) -> Vec<(i64, Vec<i64>)>
+      where F: Fn(&[i64]) -> i64 + Sync
+    {
+        let n = self.candidates.len();
+        let prefixes: Vec<Vec<usize>> = if self.length == 0 { vec![vec![]] } else { (0..n).map(|x| vec![x]).collect() };
+        let per_prefix: usize = (1..self.length).map(|x| n.saturating_sub(x)).product();
+        let next = AtomicUsize::new(0);
+        let (score, prefixes, next) = (&score, &prefixes, &next);
+
+        let mut best = thread::scope(|scope| {
+            let workers: Vec<_> = (0..self.threads).map(|_| scope.spawn(move || {
+                let mut best = vec![];
+                loop {
+                    let unit = next.fetch_add(1, Ordering::Relaxed);
+                    let prefix = match prefixes.get(unit) {
+                        Some(prefix) => prefix,
+                        None         => break,
+                    };
+                    for (idx, setting) in Permutations::starting_with(&self.candidates, self.length, prefix).enumerate() {
+                        self.offer(&mut best, (score(&setting), unit * per_prefix + idx, setting));
+                    }
+                }
+                best
+            })).collect();
+
+            let mut best = vec![];
+            for worker in workers {
+                for found in worker.join().expect("search thread died") {
+                    self.offer(&mut best, found);
+                }
+            }
+            best
+        });
+
+        best.drain(..).map(|(score, _, setting)| (score, setting)).collect()
+    }
+
+    /// The single best setting and its score, if there are any settings.
+    #[cfg(test)]
+    pub fn best<F>(&self, score: F) -> Option<(i64, Vec<i64>)>
+      where F: Fn(&[i64]) -> i64 + Sync
+    {
+        self.run(score).into_iter().next()
+    }
+
+    fn beats(&self, a: &(i64, usize, Vec<i64>), b: &(i64, usize, Vec<i64>)) -> bool {
+        match self.goal {
+            Goal::Maximize => a.0 > b.0 || (a.0 == b.0 && a.1 < b.1),
+            Goal::Minimize => a.0 < b.0 || (a.0 == b.0 && a.1 < b.1),
+        }
+    }
+
+    fn offer(&self, best: &mut Vec<(i64, usize, Vec<i64>)>, found: (i64, usize, Vec<i64>)) {
+        let place = best.iter().position(|x| self.beats(&found, x)).unwrap_or(best.len());
+        if place < self.keep {
+            best.insert(place, found);
+            best.truncate(self.keep);
+        }
+    }
+}
+
+#[test]
+fn searching() {
+    let all: Vec<Vec<i64>> = Permutations::new(&[1,2,3], 3).collect();
+    assert_eq!(all, vec![vec![1,2,3], vec![1,3,2], vec![2,1,3], vec![2,3,1], vec![3,1,2], vec![3,2,1]]);
+    let pairs: Vec<Vec<i64>> = Permutations::new(&[5,6,7], 2).collect();
+    assert_eq!(pairs, vec![vec![5,6], vec![5,7], vec![6,5], vec![6,7], vec![7,5], vec![7,6]]);
+    assert_eq!(Permutations::new(&[1,2], 3).count(), 0);
+    assert_eq!(Permutations::new(&[1,2], 0).collect::<Vec<_>>(), vec![Vec::<i64>::new()]);
+    assert_eq!(Permutations::new(&[0,1,2,3,4,5,6], 7).count(), 5040);
+    let from_two: Vec<Vec<i64>> = Permutations::starting_with(&[5,6,7], 2, &[1]).collect();
+    assert_eq!(from_two, vec![vec![6,5], vec![6,7]]);
+
+    // read the setting as a number
+    let value = |x: &[i64]| x.iter().fold(0, |acc, d| acc * 10 + d);
+    assert_eq!(Search::new(&[3,1,4,2]).best(value), Some((4321, vec![4,3,2,1])));
+    assert_eq!(Search::new(&[3,1,4,2]).goal(Goal::Minimize).best(value), Some((1234, vec![1,2,3,4])));
+    assert_eq!(Search::new(&[1,2,3,4,5]).length(2).top(3).run(value),
+               vec![(54, vec![5,4]), (53, vec![5,3]), (52, vec![5,2])]);
+    let serial = Search::new(&[0,1,2,3,4,5]).top(10).run(|x| value(x) % 97);
+    assert_eq!(Search::new(&[0,1,2,3,4,5]).top(10).threads(4).run(|x| value(x) % 97), serial);
+    let serial = Search::new(&[0,1,2,3,4,5]).length(3).top(10).run(|x| value(x) % 7);
+    assert_eq!(Search::new(&[0,1,2,3,4,5]).length(3).top(10).threads(8).run(|x| value(x) % 7), serial);
+
+    // ties go to the earliest setting, however the work is split
+    assert_eq!(Search::new(&[1,2,3]).threads(3).best(|_| 0), Some((0, vec![1,2,3])));
+    assert_eq!(Search::new(&[1,2,3]).threads(2).length(2).best(|_| 0), Some((0, vec![1,2])));
+    assert_eq!(Search::new(&[1,2,3]).length(4).best(value), None);
+    assert_eq!(Search::new(&[1,2,3]).length(0).run(value), vec![(0, vec![])]);
+}
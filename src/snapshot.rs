@@ -0,0 +1,214 @@
+use crate::machine::{Computer, MachineError};
+use crate::memory::Memory;
+use std::fmt;
+use std::fs;
+use std::str::FromStr;
+
+const HEADER: &str = "intcode snapshot 1";
+
+/// A saved machine, along with any input we'd queued up for it but it hadn't
+/// read yet. The file format is plain text, one field per line:
+///
+/// ```text
+/// intcode snapshot 1
+/// position 1012
+/// relative_base 4375
+/// done false
+/// awaiting_input none
+/// inputs 110,111,114,116,104,10
+/// memory 109,4795,21102,3124,1,1,...
+/// sparse 5000=12,1000000=-3
+/// ```
+///
+/// `awaiting_input` is the address a machine stopped at an `INPUT` will
+/// write to, or `none`. `memory` is the program image; `sparse` holds any
+/// non-zero cells the program wrote beyond it, and is left out if there
+/// aren't any.
+///
+/// There's no room for a dialect, checked arithmetic, or a budget, so a
+/// restored machine always runs the standard set, wraps, and has no limits;
+/// `save` refuses machines that don't.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    pub computer: Computer,
+    pub inputs: Vec<i64>,
+}
+
+impl Snapshot {
+    pub fn new(computer: Computer, inputs: &[i64]) -> Snapshot {
+        Snapshot{ computer, inputs: inputs.to_vec() }
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), MachineError> {
+        if let Some(problem) = self.computer.customization() {
+            return Err(MachineError::Unsaveable(problem.to_string()));
+        }
+        fs::write(path, self.to_string())?;
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> Result<Snapshot, MachineError> {
+        let contents = fs::read_to_string(path)?;
+        Snapshot::from_str(&contents)
+    }
+}
+
+fn write_list(f: &mut fmt::Formatter, name: &str, values: &[i64]) -> fmt::Result {
+    let strs: Vec<String> = values.iter().map(|x| x.to_string()).collect();
+    writeln!(f, "{} {}", name, strs.join(","))
+}
+
+impl fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        writeln!(f, "position {}", self.computer.position())?;
+        writeln!(f, "relative_base {}", self.computer.relative_base())?;
+        writeln!(f, "done {}", self.computer.is_done())?;
+        match self.computer.awaiting_input() {
+            None       => writeln!(f, "awaiting_input none")?,
+            Some(dest) => writeln!(f, "awaiting_input {}", dest)?,
+        }
+        write_list(f, "inputs", &self.inputs)?;
+        write_list(f, "memory", self.computer.memory())?;
+        let sparse = self.computer.full_memory().sparse_cells();
+        if !sparse.is_empty() {
+            let strs: Vec<String> = sparse.iter().map(|(addr, value)| format!("{}={}", addr, value)).collect();
+            writeln!(f, "sparse {}", strs.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+/// Pull the value for the given field off the next line of the snapshot.
+fn field<'a, I>(lines: &mut I, name: &str) -> Result<(usize, &'a str), MachineError>
+    where I: Iterator<Item=(usize, &'a str)>
+{
+    match lines.next() {
+        None => Err(MachineError::Snapshot(0, format!("missing '{}'", name))),
+        Some((line, text)) => match text.split_once(' ') {
+            Some((key, value)) if key == name => Ok((line, value.trim())),
+            None if text.trim() == name       => Ok((line, "")),
+            _ => Err(MachineError::Snapshot(line, format!("expected '{}'", name))),
+        }
+    }
+}
+
+fn parse_value<T: FromStr>(line: usize, value: &str) -> Result<T, MachineError> {
+    value.parse().map_err(|_| MachineError::Snapshot(line, format!("bad value '{}'", value)))
+}
+
+fn parse_list(line: usize, value: &str) -> Result<Vec<i64>, MachineError> {
+    if value.is_empty() {
+        return Ok(vec![]);
+    }
+    value.split(',').map(|x| parse_value(line, x.trim())).collect()
+}
+
+impl FromStr for Snapshot {
+    type Err = MachineError;
+
+    fn from_str(s: &str) -> Result<Snapshot, MachineError> {
+        let mut lines = s.lines().enumerate().map(|(idx, text)| (idx + 1, text));
+
+        match lines.next() {
+            Some((_, text)) if text.trim() == HEADER => {}
+            _ => return Err(MachineError::Snapshot(1, "not an Intcode snapshot".to_string())),
+        }
+
+        let (line, value) = field(&mut lines, "position")?;
+        let position = parse_value(line, value)?;
+        let (line, value) = field(&mut lines, "relative_base")?;
+        let relative_base = parse_value(line, value)?;
+        let (line, value) = field(&mut lines, "done")?;
+        let done = parse_value(line, value)?;
+        let (line, value) = field(&mut lines, "awaiting_input")?;
+        let awaiting_input = if value == "none" { None } else { Some(parse_value(line, value)?) };
+        let (line, value) = field(&mut lines, "inputs")?;
+        let inputs = parse_list(line, value)?;
+        let (line, value) = field(&mut lines, "memory")?;
+        let mut memory = Memory::from(parse_list(line, value)?);
+
+        if let Some((line, text)) = lines.next().filter(|(_, text)| !text.trim().is_empty()) {
+            let value = match text.split_once(' ') {
+                Some(("sparse", value)) => value.trim(),
+                _ => return Err(MachineError::Snapshot(line, "expected 'sparse'".to_string())),
+            };
+            for cell in value.split(',') {
+                let (addr, val) = cell.split_once('=')
+                                      .ok_or_else(|| MachineError::Snapshot(line, format!("bad cell '{}'", cell)))?;
+                memory.set(parse_value(line, addr.trim())?, parse_value(line, val.trim())?)
+                      .map_err(|e| MachineError::Snapshot(line, e.to_string()))?;
+            }
+        }
+
+        Ok(Snapshot{ computer: Computer::from_state(memory, position, relative_base, done, awaiting_input), inputs })
+    }
+}
+
+#[test]
+fn snapshots() {
+    use crate::machine::RunResult;
+
+    // stop day 9 partway through, and make sure that picking it back up
+    // from disk gets us the same answer
+    let mut computer = Computer::load("inputs/day9").unwrap();
+    for _ in 0..5000 {
+        computer = match computer.step() {
+            RunResult::Continue(next) => next,
+            RunResult::Input(next)    => next.provide_input(2),
+            _                         => panic!("day 9 stopped early"),
+        };
+    }
+
+    let path = std::env::temp_dir().join(format!("aoc-snapshot-{}", std::process::id()));
+    let path = path.to_str().unwrap();
+    Snapshot::new(computer.clone(), &[7, 8]).save(path).unwrap();
+    let restored = Snapshot::load(path).unwrap();
+    let _ = fs::remove_file(path);
+    assert_eq!(restored.computer, computer);
+    assert_eq!(restored.inputs, vec![7, 8]);
+    assert_eq!(restored.computer.standard_run(&[]), vec![81348]);
+
+    let text = Snapshot::new(Computer::from_string("104,5,99"), &[]).to_string();
+    assert_eq!(text, "intcode snapshot 1\nposition 0\nrelative_base 0\ndone false\nawaiting_input none\ninputs \nmemory 104,5,99\n");
+    assert_eq!(Snapshot::from_str(&text).unwrap().computer, Computer::from_string("104,5,99"));
+
+    // a machine stopped at an INPUT picks up where it left off
+    let waiting = match Computer::from_string("3,9,8,9,10,9,4,9,99,-1,8").run() {
+        RunResult::Input(next) => next,
+        _                      => panic!("equals8 didn't ask for input"),
+    };
+    let text = Snapshot::new(waiting.clone(), &[]).to_string();
+    assert!(text.contains("\nposition 0\n"));
+    assert!(text.contains("\nawaiting_input 9\n"));
+    let restored = Snapshot::from_str(&text).unwrap().computer;
+    assert_eq!(restored, waiting);
+    assert_eq!(restored.provide_input(8).standard_run(&[]), vec![1]);
+
+    let bad = "intcode snapshot 1\nposition 0\nrelative_base x\n";
+    match Snapshot::from_str(bad) {
+        Err(MachineError::Snapshot(3, msg)) => assert_eq!(msg, "bad value 'x'"),
+        other => panic!("unexpected result {:?}", other),
+    }
+    assert!(Snapshot::from_str("1,2,3").is_err());
+
+    let mut far = Computer::from_string("21101,3,4,1000000,99");
+    far = match far.run() {
+        RunResult::Halted(next) => next,
+        _                       => panic!("far write didn't halt"),
+    };
+    let text = Snapshot::new(far.clone(), &[]).to_string();
+    assert!(text.ends_with("memory 21101,3,4,1000000,99\nsparse 1000000=7\n"));
+    assert_eq!(Snapshot::from_str(&text).unwrap().computer, far);
+    assert!(Snapshot::load("inputs/no-such-snapshot").is_err());
+
+    // nothing is written for a machine the format can't describe
+    use crate::machine::Arithmetic;
+    let checked = Snapshot::new(Computer::from_string("104,5,99").with_arithmetic(Arithmetic::Checked), &[]);
+    assert!(matches!(checked.save(path), Err(MachineError::Unsaveable(_))));
+    assert!(!std::path::Path::new(path).exists());
+    let budget = Snapshot::new(Computer::from_string("104,5,99").with_step_budget(3), &[]);
+    assert!(matches!(budget.save(path), Err(MachineError::Unsaveable(_))));
+    let dialect = Computer::from_string("104,5,99").with_instruction_set(std::sync::Arc::new(crate::isa::InstructionSet::debugging()));
+    assert!(matches!(Snapshot::new(dialect, &[]).save(path), Err(MachineError::Unsaveable(_))));
+}
@@ -0,0 +1,67 @@
+use crate::device::{Device, Flow};
+use crate::endchannel::{channel, Receiver, Sender};
+use crate::machine::{Computer, MachineError, RunResult};
+use std::thread::{self, JoinHandle};
+
+/// A machine's end of its input and output channels.
+struct Wires {
+    input: Receiver<i64>,
+    output: Sender<i64>,
+}
+
+impl Device for Wires {
+    fn next_input(&mut self) -> Option<i64> {
+        self.input.recv()
+    }
+
+    fn on_output(&mut self, value: i64) -> Flow {
+        // If nobody's listening any more, the machine may as well carry on.
+        self.output.send_ignore_error(value);
+        Flow::Continue
+    }
+}
+
+/// Run the given machine on its own thread. It reads its input from the
+/// returned sender, and writes its output to the returned receiver, which
+/// closes when the machine halts. Closing the sender while the machine is
+/// waiting for input stops it there.
+///
+/// The thread comes back with the final machine, either halted, waiting for
+/// input or out of budget, or with whatever error stopped it.
+pub fn spawn_machine(computer: Computer) -> (Sender<i64>, Receiver<i64>, JoinHandle<Result<Computer, MachineError>>) {
+    let (input_sender, input) = channel();
+    let (output, output_receiver) = channel();
+
+    let handle = thread::spawn(move || {
+        let mut wires = Wires{ input, output };
+
+        match computer.run_with(&mut wires) {
+            RunResult::Halted(next)      => Ok(next),
+            RunResult::Input(next)       => Ok(next),
+            RunResult::OutOfBudget(next) => Ok(next),
+            RunResult::Error(e)          => Err(e),
+            _                            => unreachable!("wires never stop a machine"),
+        }
+    });
+
+    (input_sender, output_receiver, handle)
+}
+
+#[test]
+fn spawning() {
+    let (input, output, handle) = spawn_machine(Computer::load("inputs/day9").unwrap());
+    input.send(1);
+    assert_eq!(output.collect::<Vec<i64>>(), vec![3063082071]);
+    assert!(handle.join().unwrap().unwrap().is_done());
+
+    // closing the input leaves the machine waiting for more
+    let (mut input, mut output, handle) = spawn_machine(Computer::from_string("3,7,4,7,1105,1,0,0"));
+    input.send(4);
+    assert_eq!(output.recv(), Some(4));
+    input.conclude();
+    assert_eq!(output.recv(), None);
+    assert_eq!(handle.join().unwrap().unwrap().awaiting_input(), Some(7));
+
+    let (_input, _output, handle) = spawn_machine(Computer::from_string("1,0,0,0,77"));
+    assert!(handle.join().unwrap().is_err());
+}
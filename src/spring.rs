@@ -1,5 +1,5 @@
-use crate::machine::{Computer, RunResult};
-use std::collections::VecDeque;
+use crate::ascii::Ascii;
+use crate::machine::Computer;
 
 struct Jumper {
     result: i64
@@ -7,36 +7,18 @@ struct Jumper {
 
 impl Jumper {
     fn new(file: &str, program: Program, command: &str) -> Jumper {
-        let mut computer = Computer::load(file);
-        let mut encoding = program.encode();
-
-        for c in command.chars() {
-            encoding.push_back(c as u8 as i64);
+        let computer = Computer::load(file).expect("Couldn't load Intcode program");
+        let mut droid = Ascii::new(computer);
 
+        for line in program.encode() {
+            droid.send_line(&line);
         }
-        encoding.push_back('\n' as u8 as i64);
-        let result = loop {
-            match computer.run() {
-                RunResult::Continue(next) => computer = next,
-                RunResult::Halted(_) => panic!("Machine halted?!"),
-                RunResult::Output(o, next) => {
-                    if o > 128 {
-                        break o;
-                    }
-                    print!("{}", o as u8 as char);
-                    computer = next;
-                }
-                RunResult::Input(function) => {
-                    match encoding.pop_front() {
-                        None =>
-                            panic!("Ran out of input?!"),
-                        Some(x) =>
-                            computer = function(x),
-                    }
-                }
-            }
-        };
-
+        droid.send_line(command);
+        if let Err(e) = droid.run() {
+            panic!("Springdroid failed: {}", e);
+        }
+        print!("{}", droid.take_text());
+        let result = *droid.take_values().first().expect("Springdroid didn't report any damage");
 
         Jumper{ result }
     }
@@ -94,14 +76,8 @@ impl Program {
         }
     }
 
-    fn encode(&self) -> VecDeque<i64> {
-        let mut res = VecDeque::new();
-
-        for part in self.parts.iter() {
-            res.extend(part.encode());
-        }
-
-        res
+    fn encode(&self) -> Vec<String> {
+        self.parts.iter().map(|x| x.encode()).collect()
     }
 }
 
@@ -113,25 +89,14 @@ enum Instruction {
 }
 
 impl Instruction {
-    fn encode(&self) -> Vec<i64> {
-        let (mut res, x, y) = match self {
-            Instruction::And(x, y) => (vec!['A' as u8 as i64,
-                                            'N' as u8 as i64,
-                                            'D' as u8 as i64], x, y),
-            Instruction::Or(x, y)  => (vec!['O' as u8 as i64,
-                                            'R' as u8 as i64], x, y),
-            Instruction::Not(x, y) => (vec!['N' as u8 as i64,
-                                            'O' as u8 as i64,
-                                            'T' as u8 as i64], x, y),
+    fn encode(&self) -> String {
+        let (name, x, y) = match self {
+            Instruction::And(x, y) => ("AND", x, y),
+            Instruction::Or(x, y)  => ("OR", x, y),
+            Instruction::Not(x, y) => ("NOT", x, y),
         };
 
-        res.push(' ' as u8 as i64);
-        res.push(x.encode());
-        res.push(' ' as u8 as i64);
-        res.push(y.encode());
-        res.push('\n' as u8 as i64);
-
-        res
+        format!("{} {} {}", name, x.encode(), y.encode())
     }
 }
 
@@ -151,19 +116,19 @@ enum Register {
 }
 
 impl Register {
-    fn encode(&self) -> i64 {
+    fn encode(&self) -> char {
         match self {
-            Register::Temp => 'T' as u8 as i64,
-            Register::OneAway => 'A' as u8 as i64,
-            Register::TwoAway => 'B' as u8 as i64,
-            Register::ThreeAway => 'C' as u8 as i64,
-            Register::FourAway => 'D' as u8 as i64,
-            Register::FiveAway => 'E' as u8 as i64,
-            Register::SixAway => 'F' as u8 as i64,
-            Register::SevenAway => 'G' as u8 as i64,
-            Register::EightAway => 'H' as u8 as i64,
-            Register::NineAway => 'I' as u8 as i64,
-            Register::Jump => 'J' as u8 as i64,
+            Register::Temp => 'T',
+            Register::OneAway => 'A',
+            Register::TwoAway => 'B',
+            Register::ThreeAway => 'C',
+            Register::FourAway => 'D',
+            Register::FiveAway => 'E',
+            Register::SixAway => 'F',
+            Register::SevenAway => 'G',
+            Register::EightAway => 'H',
+            Register::NineAway => 'I',
+            Register::Jump => 'J',
         }
     }
 }
\ No newline at end of file
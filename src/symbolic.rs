@@ -0,0 +1,722 @@
+use crate::machine::{Computer, Fault, Instruction, Mode};
+use crate::machine::{ADD, ADJUST_BASE, EQUALS, HALT, INPUT, JMPIF, JMPNIF, LESS_THAN, MULTIPLY, OUTPUT};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::TryFrom;
+use std::fmt;
+use std::rc::Rc;
+
+const DEFAULT_MAX_STEPS: u64 = 100_000;
+const DEFAULT_MAX_PATHS: usize = 1000;
+
+/// A value worked out from the program's inputs: a polynomial in them. Each
+/// term is the inputs multiplied together in it, in order, so `[0, 0, 1]`
+/// is `in0*in0*in1`, with its coefficient. Cells wrap around, and so do the
+/// coefficients, so this is always exactly what a machine would compute.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Expr {
+    constant: i64,
+    terms: Terms,
+}
+
+type Terms = BTreeMap<Vec<usize>, i64>;
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+impl Expr {
+    pub fn constant(value: i64) -> Expr {
+        Expr{ constant: value, terms: BTreeMap::new() }
+    }
+
+    /// The nth value the program read, counting from zero.
+    pub fn input(n: usize) -> Expr {
+        Expr{ constant: 0, terms: vec![(vec![n], 1)].into_iter().collect() }
+    }
+
+    fn add_term(&mut self, term: Vec<usize>, coefficient: i64) {
+        let sum = self.terms.get(&term).cloned().unwrap_or(0).wrapping_add(coefficient);
+        if sum == 0 {
+            self.terms.remove(&term);
+        } else {
+            self.terms.insert(term, sum);
+        }
+    }
+
+    pub fn add(&self, other: &Expr) -> Expr {
+        let mut result = self.clone();
+        result.constant = result.constant.wrapping_add(other.constant);
+        for (term, coefficient) in other.terms.iter() {
+            result.add_term(term.clone(), *coefficient);
+        }
+        result
+    }
+
+    pub fn multiply(&self, other: &Expr) -> Expr {
+        let mut result = Expr::constant(self.constant.wrapping_mul(other.constant));
+        for (term, coefficient) in self.terms.iter() {
+            result.add_term(term.clone(), coefficient.wrapping_mul(other.constant));
+        }
+        for (term, coefficient) in other.terms.iter() {
+            result.add_term(term.clone(), coefficient.wrapping_mul(self.constant));
+        }
+        for (term1, coefficient1) in self.terms.iter() {
+            for (term2, coefficient2) in other.terms.iter() {
+                let mut term: Vec<usize> = term1.iter().chain(term2.iter()).cloned().collect();
+                term.sort_unstable();
+                result.add_term(term, coefficient1.wrapping_mul(*coefficient2));
+            }
+        }
+        result
+    }
+
+    fn subtract(&self, other: &Expr) -> Expr {
+        self.add(&other.multiply(&Expr::constant(-1)))
+    }
+
+    /// The value, if it doesn't depend on the inputs.
+    pub fn known(&self) -> Option<i64> {
+        if self.terms.is_empty() { Some(self.constant) } else { None }
+    }
+
+    /// The part of this that depends on the inputs, divided through so
+    /// that its highest term is positive and its coefficients have no
+    /// common factor, along with what it was divided by. Two expressions
+    /// that are a multiple of each other, give or take a constant, come out
+    /// the same. Nothing, if dividing through would overflow a cell.
+    fn normalized(&self) -> Option<(Terms, i64)> {
+        let (_, leading) = self.terms.iter().next_back()?;
+        let divisor = self.terms.values().fold(0, |x, y| gcd(x, y.unsigned_abs()));
+        let divisor = i64::try_from(divisor).ok()? * leading.signum();
+        let terms = self.terms.iter().map(|(term, x)| Some((term.clone(), x.checked_div(divisor)?))).collect::<Option<Terms>>()?;
+        Some((terms, divisor))
+    }
+
+    /// The value this comes to, given the inputs. Panics if it needs more
+    /// inputs than that.
+    pub fn evaluate(&self, inputs: &[i64]) -> i64 {
+        self.terms.iter().fold(self.constant, |sum, (term, coefficient)| {
+            let product = term.iter().fold(*coefficient, |product, n| {
+                product.wrapping_mul(*inputs.get(*n).unwrap_or_else(|| panic!("no value for in{}", n)))
+            });
+            sum.wrapping_add(product)
+        })
+    }
+
+    /// Write out the terms that depend on the inputs, highest first, or `0`
+    /// if there aren't any, and with every sign flipped if asked.
+    fn write_terms(&self, f: &mut fmt::Formatter, flip: bool) -> fmt::Result {
+        if self.terms.is_empty() {
+            return write!(f, "0");
+        }
+        for (idx, (term, coefficient)) in self.terms.iter().rev().enumerate() {
+            let coefficient = if flip { coefficient.wrapping_neg() } else { *coefficient };
+            let names: Vec<String> = term.iter().map(|n| format!("in{}", n)).collect();
+            let sign = match (idx == 0, coefficient < 0) {
+                (true, false)  => "",
+                (true, true)   => "-",
+                (false, false) => " + ",
+                (false, true)  => " - ",
+            };
+            match coefficient.unsigned_abs() {
+                1 => write!(f, "{}{}", sign, names.join("*"))?,
+                x => write!(f, "{}{}*{}", sign, x, names.join("*"))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.terms.is_empty() {
+            return write!(f, "{}", self.constant);
+        }
+        self.write_terms(f, false)?;
+        match self.constant {
+            0          => Ok(()),
+            x if x < 0 => write!(f, " - {}", x.unsigned_abs()),
+            x          => write!(f, " + {}", x),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Relation {
+    Less,
+    AtLeast,
+    Equal,
+    NotEqual,
+}
+
+impl Relation {
+    fn negate(self) -> Relation {
+        match self {
+            Relation::Less     => Relation::AtLeast,
+            Relation::AtLeast  => Relation::Less,
+            Relation::Equal    => Relation::NotEqual,
+            Relation::NotEqual => Relation::Equal,
+        }
+    }
+
+    fn holds(self, value: i64) -> bool {
+        match self {
+            Relation::Less     => value < 0,
+            Relation::AtLeast  => value >= 0,
+            Relation::Equal    => value == 0,
+            Relation::NotEqual => value != 0,
+        }
+    }
+}
+
+/// Something a path assumes about its inputs: that the expression stands
+/// in the relation to zero. Comparing two values is taken as comparing
+/// their difference with zero, which is only wrong when the difference
+/// doesn't fit in a cell.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Constraint {
+    pub expr: Expr,
+    pub relation: Relation,
+}
+
+impl Constraint {
+    fn negate(&self) -> Constraint {
+        Constraint{ expr: self.expr.clone(), relation: self.relation.negate() }
+    }
+
+    /// Whether the machine would find this true, given the inputs.
+    pub fn holds(&self, inputs: &[i64]) -> bool {
+        self.relation.holds(self.expr.evaluate(inputs))
+    }
+}
+
+impl fmt::Display for Constraint {
+    /// Written with the constant moved over to the right, and turned around
+    /// if need be so the highest term on the left is positive.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let flip = self.expr.terms.values().next_back().map(|x| *x < 0).unwrap_or(false);
+        let relation = match (self.relation, flip) {
+            (Relation::Less, false)    => "<",
+            (Relation::Less, true)     => ">",
+            (Relation::AtLeast, false) => ">=",
+            (Relation::AtLeast, true)  => "<=",
+            (Relation::Equal, _)       => "==",
+            (Relation::NotEqual, _)    => "!=",
+        };
+        let constant = if flip { self.expr.constant } else { self.expr.constant.wrapping_neg() };
+        self.expr.write_terms(f, flip)?;
+        write!(f, " {} {}", relation, constant)
+    }
+}
+
+/// The values some normalized expression can still take on a path, as an
+/// inclusive range. It's wider than a cell, so that working it out can't
+/// overflow.
+type Bounds = (i128, i128);
+
+const UNBOUNDED: Bounds = (i64::MIN as i128, i64::MAX as i128);
+
+fn floor_div(a: i128, b: i128) -> i128 {
+    let quotient = a / b;
+    if a % b != 0 && (a < 0) != (b < 0) { quotient - 1 } else { quotient }
+}
+
+fn ceil_div(a: i128, b: i128) -> i128 {
+    -floor_div(-a, b)
+}
+
+/// How many values a cell holds; working things out wider than a cell, a
+/// value wraps around by some multiple of this.
+const CELL: i128 = 1 << 64;
+
+fn hull((low1, high1): Bounds, (low2, high2): Bounds) -> Bounds {
+    match (low1 <= high1, low2 <= high2) {
+        (false, _)   => (low2, high2),
+        (_, false)   => (low1, high1),
+        (true, true) => (low1.min(low2), high1.max(high2)),
+    }
+}
+
+/// Narrow the bounds on some `x` to the values where `c * x + d`, worked out
+/// exactly, stands in the relation to zero.
+fn narrow_exact((low, high): Bounds, c: i128, d: i128, relation: Relation) -> Bounds {
+    match relation {
+        // c * x <= -d - 1
+        Relation::Less if c > 0    => (low, high.min(floor_div(-d - 1, c))),
+        Relation::Less             => (low.max(ceil_div(-d - 1, c)), high),
+        // c * x >= -d
+        Relation::AtLeast if c > 0 => (low.max(ceil_div(-d, c)), high),
+        Relation::AtLeast          => (low, high.min(floor_div(-d, c))),
+        Relation::Equal if d % c == 0 => (low.max(-d / c), high.min(-d / c)),
+        Relation::Equal            => (1, 0),
+        Relation::NotEqual if d % c == 0 => {
+            let x = -d / c;
+            (if low == x { low + 1 } else { low }, if high == x { high - 1 } else { high })
+        }
+        Relation::NotEqual         => (low, high),
+    }
+}
+
+/// Narrow the bounds on some `x` to the values where `c * x + d` stands in
+/// the relation to zero, as a machine would work it out. Where that wraps
+/// around, each stretch of the bounds that wraps the same number of times
+/// is narrowed on its own. If there are too many of them, this gives up and
+/// leaves the bounds alone, which only means ruling out fewer paths.
+fn narrow((low, high): Bounds, c: i64, d: i64, relation: Relation) -> Bounds {
+    if low > high {
+        return (low, high);
+    }
+    let (c, d) = (c as i128, d as i128);
+    let wraps = |x: i128| floor_div(c * x + d - UNBOUNDED.0, CELL);
+    let (first, last) = (wraps(low).min(wraps(high)), wraps(low).max(wraps(high)));
+    if last - first > 2 {
+        return (low, high);
+    }
+    (first..=last).fold((1, 0), |bounds, k| {
+        let d = d - k * CELL;
+        let stretch = narrow_exact(narrow_exact((low, high), c, d - UNBOUNDED.0, Relation::AtLeast),
+                                   c, d - UNBOUNDED.1 - 1, Relation::Less);
+        hull(bounds, narrow_exact(stretch, c, d, relation))
+    })
+}
+
+/// Why a path stopped.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Ending {
+    Halted,
+    /// The path ran into the step limit.
+    OutOfSteps,
+    /// The instruction at this address failed.
+    Fault(usize, Fault),
+    /// The instruction at this address needed to know something that
+    /// depends on the inputs, like an address, to go on.
+    Symbolic(usize, &'static str),
+}
+
+impl fmt::Display for Ending {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Ending::Halted               => write!(f, "halted"),
+            Ending::OutOfSteps           => write!(f, "ran out of steps"),
+            Ending::Fault(address, x)    => write!(f, "{} at address {}", x, address),
+            Ending::Symbolic(address, x) => write!(f, "needed a known {} at address {}", x, address),
+        }
+    }
+}
+
+/// One way through the program: what it assumed about the inputs to go
+/// that way, and what it wrote out.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Path {
+    pub constraints: Vec<Constraint>,
+    pub outputs: Vec<Expr>,
+    /// How many inputs the path read.
+    pub inputs: usize,
+    pub steps: u64,
+    pub ending: Ending,
+}
+
+impl Path {
+    /// Whether these inputs would send a machine down this path. There have
+    /// to be enough of them to get to the end of it.
+    pub fn matches(&self, inputs: &[i64]) -> bool {
+        self.inputs <= inputs.len() && self.constraints.iter().all(|x| x.holds(inputs))
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let constraints: Vec<String> = self.constraints.iter().map(|x| x.to_string()).collect();
+        let outputs: Vec<String> = self.outputs.iter().map(|x| x.to_string()).collect();
+        writeln!(f, "  when: {}", if constraints.is_empty() { "always".to_string() } else { constraints.join(", ") })?;
+        writeln!(f, "  outputs: {}", outputs.join(", "))?;
+        write!(f, "  {} after {} steps, reading {} inputs", self.ending, self.steps, self.inputs)
+    }
+}
+
+/// The machine partway down a path. Memory is the program image, shared
+/// between every path, plus whatever this path has written.
+#[derive(Clone)]
+struct State {
+    image: Rc<Vec<i64>>,
+    writes: HashMap<usize, Expr>,
+    position: usize,
+    relative_base: i64,
+    constraints: Vec<Constraint>,
+    bounds: HashMap<Terms, Bounds>,
+    outputs: Vec<Expr>,
+    inputs: usize,
+    steps: u64,
+}
+
+impl State {
+    fn get(&self, idx: usize) -> Expr {
+        match self.writes.get(&idx) {
+            Some(x) => x.clone(),
+            None    => Expr::constant(self.image.get(idx).cloned().unwrap_or(0)),
+        }
+    }
+
+    fn known(&self, expr: Expr, what: &'static str) -> Result<i64, Ending> {
+        expr.known().ok_or(Ending::Symbolic(self.position, what))
+    }
+
+    fn address(&self, ptr: i64) -> Result<usize, Ending> {
+        if ptr < 0 {
+            return Err(Ending::Fault(self.position, Fault::NegativeAddress(ptr)));
+        }
+        Ok(ptr as usize)
+    }
+
+    fn relative(&self, offset: i64) -> Result<usize, Ending> {
+        match offset.checked_add(self.relative_base) {
+            Some(ptr) => self.address(ptr),
+            None      => Err(Ending::Fault(self.position, Fault::Overflow(offset, self.relative_base))),
+        }
+    }
+
+    fn read_arg(&self, mode: Mode, cell: usize) -> Result<Expr, Ending> {
+        let raw = self.get(cell);
+        match mode {
+            Mode::Immediate => Ok(raw),
+            Mode::Position  => Ok(self.get(self.address(self.known(raw, "address")?)?)),
+            Mode::Relative  => Ok(self.get(self.relative(self.known(raw, "address")?)?)),
+        }
+    }
+
+    fn read_dest(&self, mode: Mode, cell: usize) -> Result<usize, Ending> {
+        let raw = self.known(self.get(cell), "address")?;
+        match mode {
+            Mode::Immediate => Err(Ending::Fault(self.position, Fault::ImmediateDestination)),
+            Mode::Position  => self.address(raw),
+            Mode::Relative  => self.relative(raw),
+        }
+    }
+
+    /// The values the terms can take, going by the bounds on each input
+    /// in them. If that could be more than a cell holds, the machine might
+    /// wrap around, so we can't say.
+    fn range(&self, terms: &Terms) -> Bounds {
+        let input = |n: usize| {
+            let term: Terms = vec![(vec![n], 1)].into_iter().collect();
+            self.bounds.get(&term).cloned().unwrap_or(UNBOUNDED)
+        };
+        let multiply = |(low1, high1): Bounds, (low2, high2): Bounds| {
+            let products = [low1.checked_mul(low2), low1.checked_mul(high2), high1.checked_mul(low2), high1.checked_mul(high2)];
+            let products = products.iter().cloned().collect::<Option<Vec<i128>>>()?;
+            Some((*products.iter().min()?, *products.iter().max()?))
+        };
+        let sum = terms.iter().try_fold((0i128, 0i128), |(low, high), (term, coefficient)| {
+            let coefficient = (*coefficient as i128, *coefficient as i128);
+            let (x, y) = term.iter().try_fold(coefficient, |range, n| multiply(range, input(*n)))?;
+            Some((low.checked_add(x)?, high.checked_add(y)?))
+        });
+        match sum {
+            Some((low, high)) if low >= UNBOUNDED.0 && high <= UNBOUNDED.1 => (low, high),
+            _                                                              => UNBOUNDED,
+        }
+    }
+
+    /// What assuming the constraint would leave of the bounds on the
+    /// normalized part of its expression.
+    fn narrowed(&self, constraint: &Constraint) -> Option<(Terms, Bounds)> {
+        let (terms, divisor) = constraint.expr.normalized()?;
+        let (low, high) = self.range(&terms);
+        let bounds = self.bounds.get(&terms).map(|(x, y)| (low.max(*x), high.min(*y))).unwrap_or((low, high));
+        let bounds = narrow(bounds, divisor, constraint.expr.constant, constraint.relation);
+        Some((terms, bounds))
+    }
+
+    fn assume(&mut self, constraint: Constraint) {
+        if let Some((terms, bounds)) = self.narrowed(&constraint) {
+            self.bounds.insert(terms, bounds);
+        }
+        self.constraints.push(constraint);
+    }
+
+    /// Whether the constraint holds on this path. If we can't tell, this
+    /// path assumes it does, and a copy of the machine that assumes it
+    /// doesn't goes on the worklist, to come back to the same instruction
+    /// later and go the other way.
+    fn decide(&mut self, constraint: Constraint, worklist: &mut Vec<State>) -> bool {
+        if let Some(value) = constraint.expr.known() {
+            return constraint.relation.holds(value);
+        }
+        let negated = constraint.negate();
+        if self.constraints.contains(&constraint) {
+            return true;
+        }
+        if self.constraints.contains(&negated) {
+            return false;
+        }
+        // What we've already assumed about the same expression can rule the
+        // constraint or its opposite out.
+        let possible = |x: Option<(Terms, Bounds)>| x.map(|(_, (low, high))| low <= high).unwrap_or(true);
+        match (possible(self.narrowed(&constraint)), possible(self.narrowed(&negated))) {
+            (true, false) => return true,
+            (false, true) => return false,
+            _             => {}
+        }
+
+        let mut other = self.clone();
+        other.assume(negated);
+        worklist.push(other);
+        self.assume(constraint);
+        true
+    }
+
+    /// Run one instruction, or say why the path is over.
+    fn step(&mut self, known_inputs: &[i64], worklist: &mut Vec<State>) -> Result<(), Ending> {
+        let value = self.known(self.get(self.position), "instruction")?;
+        let instruction = Instruction::decode(value).map_err(|x| Ending::Fault(self.position, x))?;
+        let [arg1mode, arg2mode, arg3mode] = instruction.modes;
+        let position = self.position;
+
+        match instruction.opcode {
+            ADD | MULTIPLY | LESS_THAN | EQUALS => {
+                let arg1 = self.read_arg(arg1mode, position + 1)?;
+                let arg2 = self.read_arg(arg2mode, position + 2)?;
+                let dest = self.read_dest(arg3mode, position + 3)?;
+                let result = match instruction.opcode {
+                    ADD       => arg1.add(&arg2),
+                    MULTIPLY  => arg1.multiply(&arg2),
+                    LESS_THAN => {
+                        let constraint = Constraint{ expr: arg1.subtract(&arg2), relation: Relation::Less };
+                        Expr::constant(self.decide(constraint, worklist) as i64)
+                    }
+                    _ => {
+                        let constraint = Constraint{ expr: arg1.subtract(&arg2), relation: Relation::Equal };
+                        Expr::constant(self.decide(constraint, worklist) as i64)
+                    }
+                };
+
+                self.writes.insert(dest, result);
+                self.position += 4;
+            }
+            INPUT => {
+                let dest = self.read_dest(arg1mode, position + 1)?;
+                let input = match known_inputs.get(self.inputs) {
+                    Some(x) => Expr::constant(*x),
+                    None    => Expr::input(self.inputs),
+                };
+
+                self.inputs += 1;
+                self.writes.insert(dest, input);
+                self.position += 2;
+            }
+            OUTPUT => {
+                let arg1 = self.read_arg(arg1mode, position + 1)?;
+                self.outputs.push(arg1);
+                self.position += 2;
+            }
+            JMPIF | JMPNIF => {
+                let arg1 = self.read_arg(arg1mode, position + 1)?;
+                let arg2 = self.read_arg(arg2mode, position + 2)?;
+                let constraint = Constraint{ expr: arg1, relation: Relation::NotEqual };
+
+                if self.decide(constraint, worklist) == (instruction.opcode == JMPIF) {
+                    let target = self.known(arg2, "jump target")?;
+                    self.position = self.address(target)?;
+                } else {
+                    self.position += 3;
+                }
+            }
+            ADJUST_BASE => {
+                let arg1 = self.known(self.read_arg(arg1mode, position + 1)?, "relative base")?;
+                self.relative_base = self.relative_base.checked_add(arg1)
+                                                       .ok_or(Ending::Fault(position, Fault::Overflow(self.relative_base, arg1)))?;
+                self.position += 2;
+            }
+            HALT => {
+                self.steps += 1;
+                return Err(Ending::Halted);
+            }
+            _ =>
+                unreachable!("Instruction::decode accepted opcode {}", instruction.opcode),
+        }
+
+        self.steps += 1;
+        Ok(())
+    }
+}
+
+/// Runs a computer with its inputs as unknowns, following every way the
+/// program could go, up to a limit on the steps along any one path and on
+/// how many paths to report. Constraints are only checked against others
+/// on the same expression, give or take a constant and a factor, and
+/// against the bounds on each input, so some of the paths found may be
+/// impossible.
+pub struct Explorer {
+    start: State,
+    known_inputs: Vec<i64>,
+    max_steps: u64,
+    max_paths: usize,
+}
+
+impl Explorer {
+    pub fn new(computer: &Computer) -> Explorer {
+        let start = State{ image: Rc::new(computer.memory().to_vec()),
+                           writes: computer.full_memory().sparse_cells().into_iter().map(|(x, y)| (x, Expr::constant(y))).collect(),
+                           position: computer.position(),
+                           relative_base: computer.relative_base(),
+                           constraints: vec![],
+                           bounds: HashMap::new(),
+                           outputs: vec![],
+                           inputs: 0,
+                           steps: 0 };
+        Explorer{ start, known_inputs: vec![], max_steps: DEFAULT_MAX_STEPS, max_paths: DEFAULT_MAX_PATHS }
+    }
+
+    /// Use these values for the first inputs, and only treat the ones after
+    /// them as unknown.
+    pub fn inputs(mut self, inputs: &[i64]) -> Explorer {
+        self.known_inputs = inputs.to_vec();
+        self
+    }
+
+    pub fn max_steps(mut self, steps: u64) -> Explorer {
+        self.max_steps = steps;
+        self
+    }
+
+    pub fn max_paths(mut self, paths: usize) -> Explorer {
+        self.max_paths = paths;
+        self
+    }
+
+    pub fn explore(&self) -> Exploration {
+        let mut worklist = vec![self.start.clone()];
+        let mut paths = vec![];
+
+        while let Some(mut state) = worklist.pop() {
+            if paths.len() >= self.max_paths {
+                worklist.push(state);
+                break;
+            }
+
+            let ending = loop {
+                if state.steps >= self.max_steps {
+                    break Ending::OutOfSteps;
+                }
+                if let Err(ending) = state.step(&self.known_inputs, &mut worklist) {
+                    break ending;
+                }
+            };
+            paths.push(Path{ constraints: state.constraints, outputs: state.outputs, inputs: state.inputs,
+                             steps: state.steps, ending });
+        }
+
+        let complete = worklist.is_empty() && paths.iter().all(|x| x.ending != Ending::OutOfSteps);
+        Exploration{ paths, complete }
+    }
+}
+
+/// Every path an `Explorer` found. If it's not `complete`, there were more
+/// paths than the limit allowed, or some ran out of steps.
+pub struct Exploration {
+    pub paths: Vec<Path>,
+    pub complete: bool,
+}
+
+impl Exploration {
+    /// The path these inputs would take, if we found it.
+    pub fn path_for(&self, inputs: &[i64]) -> Option<&Path> {
+        self.paths.iter().find(|x| x.matches(inputs))
+    }
+}
+
+impl fmt::Display for Exploration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (idx, path) in self.paths.iter().enumerate() {
+            writeln!(f, "Path {}:\n{}", idx + 1, path)?;
+        }
+        if !self.complete {
+            writeln!(f, "Stopped at the step or path limit; there may be more paths.")?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn symbolic() {
+    let (a, b) = (Expr::input(0), Expr::input(1));
+    assert_eq!(a.add(&b).subtract(&b), a);
+    assert_eq!(a.multiply(&a).add(&a.multiply(&Expr::constant(2))).add(&Expr::constant(-5)).to_string(), "in0*in0 + 2*in0 - 5");
+    assert_eq!(a.add(&b).multiply(&a.subtract(&b)).evaluate(&[7, 3]), 40);
+    assert_eq!(Expr::constant(3).multiply(&Expr::constant(4)).known(), Some(12));
+
+    // is the input equal to 8?
+    let exploration = Explorer::new(&Computer::from_string("3,9,8,9,10,9,4,9,99,-1,8")).explore();
+    assert!(exploration.complete);
+    assert_eq!(exploration.paths.len(), 2);
+    let equal = exploration.path_for(&[8]).unwrap();
+    assert_eq!(equal.constraints.iter().map(|x| x.to_string()).collect::<Vec<_>>(), vec!["in0 == 8"]);
+    assert_eq!(equal.outputs, vec![Expr::constant(1)]);
+    assert_eq!(equal.ending, Ending::Halted);
+    assert_eq!(exploration.path_for(&[3]).unwrap().outputs, vec![Expr::constant(0)]);
+    assert!(exploration.path_for(&[]).is_none());
+
+    let program = Computer::from_string("3,21,1008,21,8,20,1005,20,22,107,8,21,20,1006,20,31,\
+                                         1106,0,36,98,0,0,1002,21,125,20,4,20,1105,1,46,104,\
+                                         999,1105,1,46,1101,1000,1,20,4,20,1105,1,46,98,99");
+    let exploration = Explorer::new(&program).explore();
+    assert!(exploration.complete);
+    assert_eq!(exploration.paths.len(), 3);
+    for (input, output) in [(-4, 999), (8, 1000), (192, 1001)].iter() {
+        let path = exploration.path_for(&[*input]).unwrap();
+        assert_eq!(path.outputs.iter().map(|x| x.evaluate(&[*input])).collect::<Vec<_>>(), vec![*output]);
+    }
+    let exploration = Explorer::new(&program).inputs(&[8]).explore();
+    assert_eq!(exploration.paths.len(), 1);
+    assert_eq!(exploration.paths[0].inputs, 1);
+    assert!(exploration.paths[0].constraints.is_empty());
+    assert_eq!(exploration.paths[0].outputs, vec![Expr::constant(1000)]);
+
+    let exploration = Explorer::new(&Computer::from_string("1105,1,0")).max_steps(50).explore();
+    assert!(!exploration.complete);
+    assert_eq!(exploration.paths[0].ending, Ending::OutOfSteps);
+    assert_eq!(exploration.paths[0].steps, 50);
+    let exploration = Explorer::new(&program).max_paths(2).explore();
+    assert!(!exploration.complete);
+    assert_eq!(exploration.paths.len(), 2);
+
+    // every point the tractor beam program's paths cover should give what
+    // running it does
+    let day19 = Computer::load("inputs/day19").unwrap();
+    let exploration = Explorer::new(&day19).explore();
+    assert!(exploration.complete);
+    let mut covered = 0;
+    for x in 0..10 {
+        for y in 0..10 {
+            if let Some(path) = exploration.path_for(&[x, y]) {
+                let outputs: Vec<i64> = path.outputs.iter().map(|z| z.evaluate(&[x, y])).collect();
+                assert_eq!(outputs, day19.clone().standard_run(&[x, y]));
+                covered += 1;
+            }
+        }
+    }
+    assert_eq!(covered, 100);
+
+    // narrowing follows the machine around when it wraps
+    assert_eq!(narrow(UNBOUNDED, 1, -8, Relation::Less), (i64::MIN as i128 + 8, 7));
+    assert_eq!(narrow(UNBOUNDED, 1, -8, Relation::Equal), (8, 8));
+    assert_eq!(narrow((0, i64::MAX as i128), 2, 0, Relation::Less), (1 << 62, i64::MAX as i128));
+
+    // a coefficient of i64::MIN can't be divided through to normalize it
+    let program = Computer::from_string("3,100,3,101,1002,100,-9223372036854775808,102,1002,101,-1,103,\
+                                         1,102,103,104,1005,104,0,99");
+    let exploration = Explorer::new(&program).max_steps(8).explore();
+    assert_eq!(exploration.paths.len(), 2);
+    assert_eq!(exploration.path_for(&[1, i64::MIN]).unwrap().ending, Ending::Halted);
+    assert_eq!(exploration.path_for(&[1, 0, 0, 0]).unwrap().ending, Ending::OutOfSteps);
+
+    // so doubling a non-negative input can still come out negative
+    let program = Computer::from_string("3,100,1007,100,0,101,1005,101,19,102,2,100,102,1007,102,0,103,4,103,99");
+    let exploration = Explorer::new(&program).explore();
+    assert!(exploration.complete);
+    for input in [5, 1 << 62].iter() {
+        let path = exploration.path_for(&[*input]).unwrap();
+        let outputs: Vec<i64> = path.outputs.iter().map(|x| x.evaluate(&[*input])).collect();
+        assert_eq!(outputs, program.clone().standard_run(&[*input]));
+    }
+}
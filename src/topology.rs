@@ -0,0 +1,365 @@
+use crate::device::{Device, Flow};
+use crate::endchannel::Sender;
+use crate::machine::{Computer, MachineError, RunResult};
+use crate::spawn::spawn_machine;
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+#[derive(Debug)]
+pub enum NetworkError {
+    UnknownMachine(String),
+    DuplicateMachine(String),
+    Failed(String, MachineError),
+    /// The machine used up its step budget or time limit before it halted.
+    OutOfBudget(String),
+    /// The machine feeds back into itself, which only `Network::run` can
+    /// tell has gone quiet.
+    Loop(String),
+}
+
+impl fmt::Display for NetworkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NetworkError::UnknownMachine(name)   => write!(f, "no machine named '{}'", name),
+            NetworkError::DuplicateMachine(name) => write!(f, "machine '{}' declared twice", name),
+            NetworkError::Failed(name, e)        => write!(f, "machine '{}' failed: {}", name, e),
+            NetworkError::OutOfBudget(name)      => write!(f, "machine '{}' ran out of budget", name),
+            NetworkError::Loop(name)             => write!(f, "machine '{}' is in a loop, so can't run on its own thread", name),
+        }
+    }
+}
+
+struct Node {
+    name: String,
+    computer: Computer,
+    inputs: VecDeque<i64>,
+    outputs: Vec<i64>,
+}
+
+/// A machine's view of the network: its own input queue, and a stop after
+/// every value it writes, so we can pass it along straight away.
+struct Port<'a> {
+    inputs: &'a mut VecDeque<i64>,
+}
+
+impl Device for Port<'_> {
+    fn next_input(&mut self) -> Option<i64> {
+        self.inputs.pop_front()
+    }
+
+    fn on_output(&mut self, _: i64) -> Flow {
+        Flow::Stop
+    }
+}
+
+/// A set of named machines wired together. Every value a machine writes goes
+/// down each of its outgoing edges, in the order they were declared, and is
+/// also kept as that machine's output. Machines with several incoming edges
+/// get their values in whatever order they turn up.
+///
+/// ```text
+/// Network::new().machine("a", computer.clone(), &[phase_a])
+///               .machine("b", computer.clone(), &[phase_b])
+///               .edge("a", "b")
+///               .input("a", 0)
+/// ```
+#[derive(Default)]
+pub struct Network {
+    nodes: Vec<Node>,
+    edges: Vec<(String, String)>,
+    feeds: Vec<(String, i64)>,
+}
+
+impl Network {
+    pub fn new() -> Network {
+        Network::default()
+    }
+
+    /// Add a machine, with the values (usually a phase setting) it reads
+    /// before anything else.
+    pub fn machine(mut self, name: &str, computer: Computer, initial: &[i64]) -> Network {
+        self.nodes.push(Node{ name: name.to_string(), computer, inputs: initial.iter().cloned().collect(), outputs: vec![] });
+        self
+    }
+
+    pub fn edge(mut self, from: &str, to: &str) -> Network {
+        self.edges.push((from.to_string(), to.to_string()));
+        self
+    }
+
+    /// Connect each of the given machines to the next.
+    pub fn chain(self, names: &[&str]) -> Network {
+        names.windows(2).fold(self, |network, pair| network.edge(pair[0], pair[1]))
+    }
+
+    /// Connect each of the given machines to the next, and the last back to
+    /// the first.
+    pub fn ring(self, names: &[&str]) -> Network {
+        match (names.first(), names.last()) {
+            (Some(first), Some(last)) => self.chain(names).edge(last, first),
+            _                         => self,
+        }
+    }
+
+    /// Send the output of one machine to each of the others.
+    pub fn fan_out(self, from: &str, to: &[&str]) -> Network {
+        to.iter().fold(self, |network, name| network.edge(from, name))
+    }
+
+    /// Send the output of each of the machines to one other.
+    pub fn fan_in(self, from: &[&str], to: &str) -> Network {
+        from.iter().fold(self, |network, name| network.edge(name, to))
+    }
+
+    /// Queue up a value for a machine from outside the network, after its
+    /// initial values.
+    pub fn input(mut self, name: &str, value: i64) -> Network {
+        self.feeds.push((name.to_string(), value));
+        self
+    }
+
+    /// A row of copies of the given machine, one per phase setting, named
+    /// `amp0`, `amp1`, and so on, with the first one fed a `0`. With
+    /// `feedback`, the last one feeds back into the first.
+    pub fn amplifiers(computer: &Computer, phases: &[i64], feedback: bool) -> Network {
+        let names: Vec<String> = (0..phases.len()).map(|idx| format!("amp{}", idx)).collect();
+        let names: Vec<&str> = names.iter().map(|x| x.as_str()).collect();
+        let network = names.iter().zip(phases.iter())
+                           .fold(Network::new(), |network, (name, phase)| network.machine(name, computer.clone(), &[*phase]));
+        let network = if feedback { network.ring(&names) } else { network.chain(&names) };
+
+        match names.first() {
+            Some(first) => network.input(first, 0),
+            None        => network,
+        }
+    }
+
+    fn index(&self, name: &str) -> Result<usize, NetworkError> {
+        self.nodes.iter().position(|x| x.name == name).ok_or_else(|| NetworkError::UnknownMachine(name.to_string()))
+    }
+
+    /// Check the names, hand out the outside inputs, and work out where each
+    /// machine's output goes.
+    fn wire(&mut self) -> Result<Vec<Vec<usize>>, NetworkError> {
+        let mut seen = HashSet::new();
+        for node in self.nodes.iter() {
+            if !seen.insert(node.name.as_str()) {
+                return Err(NetworkError::DuplicateMachine(node.name.clone()));
+            }
+        }
+
+        let mut targets = vec![vec![]; self.nodes.len()];
+        for (from, to) in self.edges.iter() {
+            let from = self.index(from)?;
+            targets[from].push(self.index(to)?);
+        }
+
+        for (name, value) in mem::take(&mut self.feeds) {
+            let idx = self.index(&name)?;
+            self.nodes[idx].inputs.push_back(value);
+        }
+
+        Ok(targets)
+    }
+
+    /// Run the network on this thread until it goes quiet: every machine has
+    /// halted, or is waiting for input nobody is going to send it. Machines
+    /// left waiting stay that way, so more input can be fed to them and the
+    /// network run again. A machine that runs out of budget stops the
+    /// network, since it might still have had something to say.
+    pub fn run(&mut self) -> Result<(), NetworkError> {
+        let targets = self.wire()?;
+
+        loop {
+            let mut moved = false;
+
+            for (idx, outgoing) in targets.iter().enumerate() {
+                let node = &mut self.nodes[idx];
+                if node.computer.is_done() {
+                    continue;
+                }
+
+                let computer = mem::take(&mut node.computer);
+                let mut port = Port{ inputs: &mut node.inputs };
+                let value = match computer.run_with(&mut port) {
+                    RunResult::Input(next)         => { node.computer = next; continue; }
+                    RunResult::OutOfBudget(next)   => { node.computer = next; return Err(NetworkError::OutOfBudget(node.name.clone())); }
+                    RunResult::Halted(next)        => { node.computer = next; moved = true; continue; }
+                    RunResult::Output(value, next) => { node.computer = next; value }
+                    RunResult::Error(e)            => return Err(NetworkError::Failed(node.name.clone(), e)),
+                    RunResult::Continue(_)         => unreachable!("run_with never stops mid-run"),
+                };
+
+                node.outputs.push(value);
+                for target in outgoing.iter() {
+                    self.nodes[*target].inputs.push_back(value);
+                }
+                moved = true;
+            }
+
+            if !moved {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Run the network with each machine on its own thread. A machine's input
+    /// only closes once everything feeding it has stopped, so machines in a
+    /// loop could wait on each other forever; a network with one is refused.
+    pub fn run_threaded(&mut self) -> Result<(), NetworkError> {
+        let targets = self.wire()?;
+        if let Some(idx) = in_loop(&targets) {
+            return Err(NetworkError::Loop(self.nodes[idx].name.clone()));
+        }
+        let mut inputs: Vec<Arc<Mutex<Sender<i64>>>> = vec![];
+        let mut stages = vec![];
+
+        for node in self.nodes.iter_mut() {
+            let (input, output, handle) = spawn_machine(mem::take(&mut node.computer));
+            for value in node.inputs.drain(..) {
+                input.send(value);
+            }
+            inputs.push(Arc::new(Mutex::new(input)));
+            stages.push((output, handle));
+        }
+
+        // Each machine gets a thread passing its output along; a machine's
+        // input closes when the last of the threads feeding it lets go.
+        let routers: Vec<_> = stages.into_iter().zip(targets.iter()).map(|((output, handle), targets)| {
+            let outgoing: Vec<_> = targets.iter().map(|x| inputs[*x].clone()).collect();
+            let router = thread::spawn(move || {
+                output.inspect(|value| {
+                    for target in outgoing.iter() {
+                        target.lock().unwrap().send_ignore_error(*value);
+                    }
+                }).collect::<Vec<i64>>()
+            });
+            (router, handle)
+        }).collect();
+        drop(inputs);
+
+        let mut failure = None;
+        for (node, (router, handle)) in self.nodes.iter_mut().zip(routers) {
+            node.outputs.extend(router.join().expect("network router thread died"));
+            match handle.join().expect("network machine thread died") {
+                Ok(computer) => {
+                    if !computer.is_done() && computer.awaiting_input().is_none() {
+                        failure.get_or_insert(NetworkError::OutOfBudget(node.name.clone()));
+                    }
+                    node.computer = computer;
+                }
+                Err(e)       => { failure.get_or_insert(NetworkError::Failed(node.name.clone(), e)); }
+            }
+        }
+
+        match failure {
+            None    => Ok(()),
+            Some(e) => Err(e),
+        }
+    }
+
+    pub fn outputs(&self, name: &str) -> Option<&[i64]> {
+        self.index(name).ok().map(|idx| &self.nodes[idx].outputs[..])
+    }
+
+    pub fn last_output(&self, name: &str) -> Option<i64> {
+        self.outputs(name).and_then(|x| x.last().cloned())
+    }
+
+    /// The machine as it was left when the network stopped.
+    pub fn computer(&self, name: &str) -> Option<&Computer> {
+        self.index(name).ok().map(|idx| &self.nodes[idx].computer)
+    }
+}
+
+/// Some machine that, following the edges, gets back to itself.
+fn in_loop(targets: &[Vec<usize>]) -> Option<usize> {
+    fn visit(idx: usize, targets: &[Vec<usize>], open: &mut [bool], done: &mut [bool]) -> Option<usize> {
+        if open[idx] {
+            return Some(idx);
+        }
+        if done[idx] {
+            return None;
+        }
+        open[idx] = true;
+        let found = targets[idx].iter().find_map(|x| visit(*x, targets, open, done));
+        open[idx] = false;
+        done[idx] = true;
+        found
+    }
+
+    let (mut open, mut done) = (vec![false; targets.len()], vec![false; targets.len()]);
+    (0..targets.len()).find_map(|idx| visit(idx, targets, &mut open, &mut done))
+}
+
+/// Run copies of the given machine as amplifiers with the given phase
+/// settings, and return the last signal out of the last one, if there are
+/// any amplifiers. A plain row runs each amplifier on its own thread; with
+/// feedback they take turns.
+pub fn amplify(computer: &Computer, phases: &[i64], feedback: bool) -> Option<i64> {
+    if phases.is_empty() {
+        return None;
+    }
+    let mut network = Network::amplifiers(computer, phases, feedback);
+    let result = if feedback { network.run() } else { network.run_threaded() };
+    if let Err(e) = result {
+        panic!("Amplifier network failed: {}", e);
+    }
+    Some(network.last_output(&format!("amp{}", phases.len() - 1)).expect("amplifiers produced no signal"))
+}
+
+#[test]
+fn networks() {
+    let double = Computer::from_string("3,0,102,2,0,0,4,0,99");
+    let add = Computer::from_string("3,11,3,12,1,11,12,13,4,13,99,0,0,0");
+    let network = || Network::new().machine("source", Computer::from_string("104,5,99"), &[])
+                                   .machine("left", double.clone(), &[])
+                                   .machine("right", double.clone(), &[])
+                                   .machine("sum", add.clone(), &[])
+                                   .fan_out("source", &["left", "right"])
+                                   .fan_in(&["left", "right"], "sum");
+
+    let mut sequential = network();
+    sequential.run().unwrap();
+    assert_eq!(sequential.outputs("left"), Some(&[10][..]));
+    assert_eq!(sequential.outputs("sum"), Some(&[20][..]));
+    assert!(sequential.computer("sum").unwrap().is_done());
+
+    let mut threaded = network();
+    threaded.run_threaded().unwrap();
+    assert_eq!(threaded.outputs("sum"), Some(&[20][..]));
+
+    // a machine nobody feeds is left waiting, and can be fed later
+    let mut waiting = Network::new().machine("sum", add.clone(), &[3]);
+    waiting.run().unwrap();
+    assert_eq!(waiting.computer("sum").unwrap().awaiting_input(), Some(12));
+    let mut waiting = waiting.input("sum", 4);
+    waiting.run().unwrap();
+    assert_eq!(waiting.last_output("sum"), Some(7));
+
+    // a feedback ring runs until everyone halts
+    let example7e = Computer::from_string("3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5");
+    let mut ring = Network::amplifiers(&example7e, &[9,8,7,6,5], true);
+    ring.run().unwrap();
+    assert_eq!(ring.last_output("amp4"), Some(139629729));
+    let mut ring = Network::amplifiers(&example7e, &[9,8,7,6,5], true);
+    assert!(matches!(ring.run_threaded(), Err(NetworkError::Loop(_))));
+    assert_eq!(amplify(&example7e, &[9,8,7,6,5], true), Some(139629729));
+    assert_eq!(amplify(&example7e, &[], true), None);
+
+    assert!(matches!(Network::new().edge("a", "b").run(), Err(NetworkError::UnknownMachine(_))));
+    let mut twice = Network::new().machine("a", add.clone(), &[]).machine("a", add.clone(), &[]);
+    assert_eq!(format!("{}", twice.run().unwrap_err()), "machine 'a' declared twice");
+    let mut broken = Network::new().machine("bad", Computer::from_string("1,0,0,0,77"), &[]);
+    assert!(matches!(broken.run(), Err(NetworkError::Failed(name, _)) if name == "bad"));
+
+    // a machine cut off by its budget isn't just quiet
+    let spin = || Network::new().machine("spin", Computer::from_string("1105,1,0").with_step_budget(10), &[]);
+    assert!(matches!(spin().run(), Err(NetworkError::OutOfBudget(name)) if name == "spin"));
+    let mut threaded = spin();
+    assert!(matches!(threaded.run_threaded(), Err(NetworkError::OutOfBudget(name)) if name == "spin"));
+    assert!(!threaded.computer("spin").unwrap().is_done());
+}
@@ -0,0 +1,185 @@
+use crate::disasm::{Line, Operand};
+use crate::isa::Role;
+use crate::machine::Instruction;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Write;
+
+/// What executing an instruction did, beyond moving on to the next one.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Effect {
+    Nothing,
+    Write(usize, i64),
+    Output(i64),
+    Jump(usize),
+    Base(i64),
+    Halt,
+}
+
+/// A single executed instruction. `mnemonic` and `roles` are its name and
+/// what it does with each argument in the instruction set the machine ran it
+/// with, `operands` the raw cells following the instruction, `arguments` the values it actually read through them, and
+/// `reads` the address each argument came from, if it wasn't immediate.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Event {
+    pub address: usize,
+    pub instruction: Instruction,
+    pub mnemonic: &'static str,
+    pub roles: &'static [Role],
+    pub operands: Vec<i64>,
+    pub arguments: Vec<i64>,
+    pub reads: Vec<Option<usize>>,
+    pub effect: Effect,
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let operands = self.operands.iter()
+                                    .zip(self.instruction.modes.iter())
+                                    .map(|(value, mode)| Operand::new(*mode, *value))
+                                    .collect();
+        let line = Line::Code{ address: self.address, instruction: self.instruction, mnemonic: self.mnemonic,
+                               roles: self.roles, operands };
+        write!(f, "{:<50} ;", line.to_string())?;
+        for argument in self.arguments.iter() {
+            write!(f, " {}", argument)?;
+        }
+        match self.effect {
+            Effect::Nothing            => Ok(()),
+            Effect::Write(addr, value) => write!(f, " => [{}] = {}", addr, value),
+            Effect::Output(value)      => write!(f, " => output {}", value),
+            Effect::Jump(addr)         => write!(f, " => goto {}", addr),
+            Effect::Base(base)         => write!(f, " => base {}", base),
+            Effect::Halt               => write!(f, " => halt"),
+        }
+    }
+}
+
+/// Something that wants to hear about every instruction a `Computer` runs.
+/// Attach one with `Computer::with_tracer`.
+pub trait Tracer: Send {
+    fn trace(&mut self, event: &Event);
+}
+
+/// Writes one line per executed instruction to the given sink.
+pub struct TraceLog<W: Write + Send> {
+    sink: W,
+}
+
+impl<W: Write + Send> TraceLog<W> {
+    pub fn new(sink: W) -> TraceLog<W> {
+        TraceLog{ sink }
+    }
+
+    /// Where the trace is going, to flush it or look at what's there.
+    pub fn sink(&mut self) -> &mut W {
+        &mut self.sink
+    }
+}
+
+impl<W: Write + Send> Tracer for TraceLog<W> {
+    fn trace(&mut self, event: &Event) {
+        // A trace that can't be written isn't worth stopping the machine for.
+        let _ = writeln!(self.sink, "{}", event);
+    }
+}
+
+/// Counts what a computer spent its time doing.
+#[derive(Debug, Default)]
+pub struct Profiler {
+    addresses: BTreeMap<usize, u64>,
+    opcodes: BTreeMap<i64, u64>,
+    mnemonics: BTreeMap<i64, &'static str>,
+    total: u64,
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler::default()
+    }
+
+    /// How many times the instruction at each address ran.
+    pub fn address_counts(&self) -> &BTreeMap<usize, u64> {
+        &self.addresses
+    }
+
+    /// How many times each opcode ran.
+    pub fn opcode_counts(&self) -> &BTreeMap<i64, u64> {
+        &self.opcodes
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// What the instructions with the given opcode were called, if any ran.
+    pub fn mnemonic(&self, opcode: i64) -> Option<&'static str> {
+        self.mnemonics.get(&opcode).cloned()
+    }
+}
+
+impl Tracer for Profiler {
+    fn trace(&mut self, event: &Event) {
+        *self.addresses.entry(event.address).or_insert(0) += 1;
+        *self.opcodes.entry(event.instruction.opcode).or_insert(0) += 1;
+        self.mnemonics.insert(event.instruction.opcode, event.mnemonic);
+        self.total += 1;
+    }
+}
+
+impl fmt::Display for Profiler {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Total instructions executed: {}", self.total())?;
+        writeln!(f, "By opcode:")?;
+        for (opcode, count) in self.opcode_counts().iter() {
+            let name = self.mnemonic(*opcode).unwrap_or("???");
+            writeln!(f, "  {:<12} {:>12}", name, count)?;
+        }
+        writeln!(f, "By address:")?;
+        for (address, count) in self.address_counts().iter() {
+            writeln!(f, "  {:08} {:>16}", address, count)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn tracing() {
+    use crate::machine::Computer;
+    use std::sync::{Arc, Mutex};
+
+    let log = Arc::new(Mutex::new(TraceLog::new(Vec::new())));
+    let computer = Computer::from_string("3,9,8,9,10,9,4,9,99,-1,8").with_tracer(log.clone());
+    assert_eq!(computer.standard_run(&[8]), vec![1]);
+    let text = String::from_utf8(log.lock().unwrap().sink().clone()).unwrap();
+    let lines: Vec<&str> = text.lines().map(|x| x.trim_end()).collect();
+    assert_eq!(lines, vec!["00000000: INPUT -> [9]                             ; => [9] = 8",
+                           "00000002: EQUALS [9], [10] -> [9]                  ; 8 8 => [9] = 1",
+                           "00000006: OUTPUT [9]                               ; 1 => output 1",
+                           "00000008: HALT                                     ; => halt"]);
+
+    let profiler = Arc::new(Mutex::new(Profiler::new()));
+    let day9 = Computer::load("inputs/day9").unwrap().with_tracer(profiler.clone());
+    let outputs = day9.standard_run(&[1]);
+    let profiler = profiler.lock().unwrap();
+    assert!(profiler.total() > 0);
+    assert_eq!(profiler.address_counts().values().sum::<u64>(), profiler.total());
+    assert_eq!(profiler.opcode_counts().values().sum::<u64>(), profiler.total());
+    assert_eq!(profiler.opcode_counts().get(&99), Some(&1));
+    assert_eq!(profiler.opcode_counts().get(&4), Some(&(outputs.len() as u64)));
+    assert!(format!("{}", profiler).starts_with(&format!("Total instructions executed: {}\n", profiler.total())));
+
+    // dialect instructions are named by the set the computer ran them with
+    use crate::isa::InstructionSet;
+    let log = Arc::new(Mutex::new(TraceLog::new(Vec::new())));
+    let profiler = Arc::new(Mutex::new(Profiler::new()));
+    let computer = Computer::from_string("104,3,10,0,99").with_instruction_set(Arc::new(InstructionSet::debugging()))
+                                                        .with_tracer(log.clone())
+                                                        .with_tracer(profiler.clone());
+    computer.standard_run(&[]);
+    let text = String::from_utf8(log.lock().unwrap().sink().clone()).unwrap();
+    assert_eq!(text.lines().nth(1).map(|x| x.trim_end()), Some("00000002: PRINT [0]                                ; 104"));
+    let profiler = profiler.lock().unwrap();
+    assert_eq!(profiler.mnemonic(10), Some("PRINT"));
+    assert!(format!("{}", profiler).contains("PRINT"));
+}
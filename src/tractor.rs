@@ -9,7 +9,7 @@ struct TractorMap {
 
 impl TractorMap {
     fn new(file: &str, side_size: usize) -> TractorMap {
-        let     base_computer = Computer::load(file);
+        let     base_computer = Computer::load(file).expect("Couldn't load Intcode program");
         let mut result = Vec::with_capacity(side_size * side_size);
         let mut affected = 0;
 
@@ -0,0 +1,277 @@
+use crate::device::{Device, Flow};
+use crate::machine::{Computer, MachineError, RunResult};
+use crate::trace::{Effect, Event, Tracer};
+use std::fmt;
+use std::fs;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+const HEADER: &str = "intcode transcript 1";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Io {
+    Input(i64),
+    Output(i64),
+}
+
+/// A value going into or out of a machine, and how many instructions the
+/// machine had run when it did, counting the one that moved the value.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Entry {
+    pub step: u64,
+    pub io: Io,
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.io {
+            Io::Input(value)  => write!(f, "input {} at step {}", value, self.step),
+            Io::Output(value) => write!(f, "output {} at step {}", value, self.step),
+        }
+    }
+}
+
+/// Everything that went into and came out of a machine, in order. The file
+/// format is plain text, one value per line:
+///
+/// ```text
+/// intcode transcript 1
+/// in 1 8
+/// out 3 1
+/// ```
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Transcript {
+    pub entries: Vec<Entry>,
+}
+
+impl Transcript {
+    pub fn save(&self, path: &str) -> Result<(), MachineError> {
+        fs::write(path, self.to_string())?;
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> Result<Transcript, MachineError> {
+        let contents = fs::read_to_string(path)?;
+        Transcript::from_str(&contents)
+    }
+}
+
+impl fmt::Display for Transcript {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        for entry in self.entries.iter() {
+            match entry.io {
+                Io::Input(value)  => writeln!(f, "in {} {}", entry.step, value)?,
+                Io::Output(value) => writeln!(f, "out {} {}", entry.step, value)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Transcript {
+    type Err = MachineError;
+
+    fn from_str(s: &str) -> Result<Transcript, MachineError> {
+        let mut lines = s.lines().enumerate().map(|(idx, text)| (idx + 1, text));
+
+        match lines.next() {
+            Some((_, text)) if text.trim() == HEADER => {}
+            _ => return Err(MachineError::Transcript(1, "not an Intcode transcript".to_string())),
+        }
+
+        let mut entries = vec![];
+        for (line, text) in lines.filter(|(_, text)| !text.trim().is_empty()) {
+            let bad = || MachineError::Transcript(line, format!("can't read '{}'", text.trim()));
+            let words: Vec<&str> = text.split_whitespace().collect();
+            let (kind, step, value) = match words[..] {
+                [kind, step, value] => (kind, step.parse().map_err(|_| bad())?, value.parse().map_err(|_| bad())?),
+                _                   => return Err(bad()),
+            };
+            let io = match kind {
+                "in"  => Io::Input(value),
+                "out" => Io::Output(value),
+                _     => return Err(bad()),
+            };
+            entries.push(Entry{ step, io });
+        }
+
+        Ok(Transcript{ entries })
+    }
+}
+
+/// Writes down every value a computer reads or writes. Attach one with
+/// `Computer::with_tracer`, and collect the transcript afterwards.
+#[derive(Debug, Default)]
+pub struct Recorder {
+    steps: u64,
+    transcript: Transcript,
+}
+
+impl Recorder {
+    pub fn new() -> Recorder {
+        Recorder::default()
+    }
+
+    pub fn transcript(&self) -> &Transcript {
+        &self.transcript
+    }
+}
+
+impl Tracer for Recorder {
+    fn trace(&mut self, event: &Event) {
+        self.steps += 1;
+        let io = match event.effect {
+            Effect::Write(_, value) if event.instruction.is_input() => Io::Input(value),
+            Effect::Output(value)                                   => Io::Output(value),
+            _                                                       => return,
+        };
+        self.transcript.entries.push(Entry{ step: self.steps, io });
+    }
+}
+
+/// Where a replay first stopped matching its transcript, counting entries
+/// from zero.
+#[derive(Debug)]
+pub enum Divergence {
+    /// The machine did something other than what was recorded.
+    Mismatch(usize, Entry, Entry),
+    /// The machine kept going after the transcript ran out.
+    Extra(usize, Entry),
+    /// The machine halted, or wanted input, before doing what was recorded.
+    Missing(usize, Entry),
+    Failed(MachineError),
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Divergence::Mismatch(idx, expected, found) =>
+                write!(f, "Entry {}: expected {}, but got {}", idx, expected, found),
+            Divergence::Extra(idx, found) =>
+                write!(f, "Entry {}: transcript is over, but got {}", idx, found),
+            Divergence::Missing(idx, expected) =>
+                write!(f, "Entry {}: expected {}, but the machine stopped", idx, expected),
+            Divergence::Failed(e) =>
+                write!(f, "Machine failed during replay: {}", e),
+        }
+    }
+}
+
+/// Feeds a machine the inputs from a transcript, checking what it records
+/// against what was recorded before.
+struct Replayer<'a> {
+    expected: &'a [Entry],
+    recorder: Arc<Mutex<Recorder>>,
+    checked: usize,
+    divergence: Option<Divergence>,
+}
+
+impl Replayer<'_> {
+    /// Check anything recorded since we last looked, returning whether it
+    /// all still matches.
+    fn check(&mut self) -> bool {
+        let recorder = self.recorder.lock().unwrap();
+
+        while self.divergence.is_none() && self.checked < recorder.transcript().entries.len() {
+            let found = recorder.transcript().entries[self.checked];
+            match self.expected.get(self.checked) {
+                None                   => self.divergence = Some(Divergence::Extra(self.checked, found)),
+                Some(x) if *x != found => self.divergence = Some(Divergence::Mismatch(self.checked, *x, found)),
+                Some(_)                => self.checked += 1,
+            }
+        }
+
+        self.divergence.is_none()
+    }
+}
+
+impl Device for Replayer<'_> {
+    fn next_input(&mut self) -> Option<i64> {
+        if !self.check() {
+            return None;
+        }
+
+        match self.expected.get(self.checked) {
+            Some(Entry{ io: Io::Input(value), .. }) => Some(*value),
+            Some(entry) => {
+                self.divergence = Some(Divergence::Missing(self.checked, *entry));
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn on_output(&mut self, _: i64) -> Flow {
+        if self.check() { Flow::Continue } else { Flow::Stop }
+    }
+}
+
+/// Run the given machine on the inputs in the transcript, checking that it
+/// produces the same outputs at the same steps, counted from wherever the
+/// machine is now. Comes back with the machine once the transcript runs out,
+/// or with the first place things went differently.
+pub fn replay(computer: Computer, transcript: &Transcript) -> Result<Computer, Divergence> {
+    let recorder = Arc::new(Mutex::new(Recorder::new()));
+    let mut replayer = Replayer{ expected: &transcript.entries, recorder: recorder.clone(), checked: 0, divergence: None };
+
+    let computer = match computer.with_tracer(recorder).run_with(&mut replayer) {
+        RunResult::Error(e)        => return Err(Divergence::Failed(e)),
+        RunResult::Halted(next)    => next,
+        RunResult::Input(next)     => next,
+        RunResult::OutOfBudget(next) => next,
+        RunResult::Output(_, next) => next,
+        RunResult::Continue(_)     => unreachable!("run_with never stops mid-run"),
+    };
+
+    replayer.check();
+    if let Some(divergence) = replayer.divergence {
+        return Err(divergence);
+    }
+    match transcript.entries.get(replayer.checked) {
+        Some(entry) => Err(Divergence::Missing(replayer.checked, *entry)),
+        None        => Ok(computer),
+    }
+}
+
+#[test]
+fn transcripts() {
+    let recorder = Arc::new(Mutex::new(Recorder::new()));
+    let computer = Computer::from_string("3,9,8,9,10,9,4,9,99,-1,8").with_tracer(recorder.clone());
+    assert_eq!(computer.standard_run(&[8]), vec![1]);
+    let transcript = recorder.lock().unwrap().transcript().clone();
+    assert_eq!(transcript.entries, vec![Entry{ step: 1, io: Io::Input(8) }, Entry{ step: 3, io: Io::Output(1) }]);
+    assert_eq!(transcript.to_string(), "intcode transcript 1\nin 1 8\nout 3 1\n");
+    assert_eq!(Transcript::from_str(&transcript.to_string()).unwrap(), transcript);
+
+    let path = std::env::temp_dir().join(format!("aoc-transcript-{}", std::process::id()));
+    let path = path.to_str().unwrap();
+    transcript.save(path).unwrap();
+    let loaded = Transcript::load(path).unwrap();
+    let _ = fs::remove_file(path);
+    assert_eq!(loaded, transcript);
+
+    assert!(replay(Computer::from_string("3,9,8,9,10,9,4,9,99,-1,8"), &transcript).unwrap().is_done());
+    // same input, but a machine comparing against 7 says something else
+    let differs = replay(Computer::from_string("3,9,8,9,10,9,4,9,99,-1,7"), &transcript).unwrap_err();
+    assert_eq!(differs.to_string(), "Entry 1: expected output 1 at step 3, but got output 0 at step 3");
+    // a machine that halts straight away never gets there
+    let halts = replay(Computer::from_string("99"), &transcript).unwrap_err();
+    assert!(matches!(halts, Divergence::Missing(0, Entry{ step: 1, io: Io::Input(8) })));
+    // one that keeps talking goes past the end
+    let chatty = replay(Computer::from_string("3,11,8,11,12,11,4,11,4,11,99,-1,8"), &transcript).unwrap_err();
+    assert!(matches!(chatty, Divergence::Extra(2, Entry{ step: 4, io: Io::Output(1) })));
+    // a transcript that stops at an input leaves the machine waiting there
+    let partial = Transcript{ entries: vec![] };
+    assert_eq!(replay(Computer::from_string("3,9,8,9,10,9,4,9,99,-1,8"), &partial).unwrap().awaiting_input(), Some(9));
+
+    assert!(matches!(Transcript::from_str("nope"), Err(MachineError::Transcript(1, _))));
+    assert!(matches!(Transcript::from_str("intcode transcript 1\nin 1 8\nsideways 2 3\n"), Err(MachineError::Transcript(3, _))));
+
+    // a longer program, recorded and played back
+    let recorder = Arc::new(Mutex::new(Recorder::new()));
+    let day9 = Computer::load("inputs/day9").unwrap();
+    assert_eq!(day9.clone().with_tracer(recorder.clone()).standard_run(&[1]), vec![3063082071]);
+    let transcript = recorder.lock().unwrap().transcript().clone();
+    assert!(replay(day9, &transcript).is_ok());
+}
@@ -0,0 +1,192 @@
+use crate::trace::{Effect, Event, Tracer};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::ops::RangeInclusive;
+
+/// Which kinds of access to a range of memory we care about.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Access {
+    Read,
+    Write,
+    Any,
+}
+
+impl Access {
+    fn covers(&self, other: Access) -> bool {
+        *self == Access::Any || *self == other
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Watchpoint {
+    pub cells: RangeInclusive<usize>,
+    pub access: Access,
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let access = match self.access {
+            Access::Read  => "reads of",
+            Access::Write => "writes to",
+            Access::Any   => "reads of or writes to",
+        };
+        write!(f, "{} {}..{}", access, self.cells.start(), self.cells.end())
+    }
+}
+
+/// A watched cell being read or written by the instruction at `address`.
+/// For a read, `value` is what was there; for a write, what went there.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hit {
+    pub address: usize,
+    pub cell: usize,
+    pub access: Access,
+    pub value: i64,
+}
+
+impl fmt::Display for Hit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.access {
+            Access::Write => write!(f, "{:08} wrote {} to {}", self.address, self.value, self.cell),
+            _             => write!(f, "{:08} read {} from {}", self.address, self.value, self.cell),
+        }
+    }
+}
+
+/// A write by the instruction at `address` onto a cell that had already run
+/// as part of an instruction.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Modification {
+    pub address: usize,
+    pub cell: usize,
+    pub value: i64,
+}
+
+impl fmt::Display for Modification {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:08} rewrote code at {} to {}", self.address, self.cell, self.value)
+    }
+}
+
+/// Keeps an eye on a computer's memory: collects a `Hit` whenever one of its
+/// watchpoints fires, and a `Modification` whenever the program writes over
+/// a cell it has executed. Cells count as executed once an instruction
+/// using them runs, opcode and operands both.
+#[derive(Debug, Default)]
+pub struct Watcher {
+    watches: Vec<Watchpoint>,
+    hits: Vec<Hit>,
+    executed: BTreeSet<usize>,
+    modifications: Vec<Modification>,
+}
+
+impl Watcher {
+    pub fn new() -> Watcher {
+        Watcher::default()
+    }
+
+    pub fn watch(&mut self, cells: RangeInclusive<usize>, access: Access) {
+        self.watches.push(Watchpoint{ cells, access });
+    }
+
+    /// Remove every watchpoint covering the given cell.
+    pub fn unwatch(&mut self, cell: usize) {
+        self.watches.retain(|x| !x.cells.contains(&cell));
+    }
+
+    pub fn watchpoints(&self) -> &[Watchpoint] {
+        &self.watches
+    }
+
+    /// The watchpoints that have fired since we last asked.
+    pub fn take_hits(&mut self) -> Vec<Hit> {
+        std::mem::take(&mut self.hits)
+    }
+
+    pub fn modifications(&self) -> &[Modification] {
+        &self.modifications
+    }
+
+    /// Whether the given cell has run as part of an instruction.
+    pub fn is_code(&self, cell: usize) -> bool {
+        self.executed.contains(&cell)
+    }
+
+    fn check(&mut self, address: usize, cell: usize, access: Access, value: i64) {
+        if self.watches.iter().any(|x| x.access.covers(access) && x.cells.contains(&cell)) {
+            self.hits.push(Hit{ address, cell, access, value });
+        }
+    }
+}
+
+impl Tracer for Watcher {
+    fn trace(&mut self, event: &Event) {
+        self.executed.extend(event.address..=event.address + event.operands.len());
+
+        for (cell, value) in event.reads.iter().zip(event.arguments.iter()) {
+            if let Some(cell) = cell {
+                self.check(event.address, *cell, Access::Read, *value);
+            }
+        }
+        if let Effect::Write(cell, value) = event.effect {
+            self.check(event.address, cell, Access::Write, value);
+            if self.is_code(cell) {
+                self.modifications.push(Modification{ address: event.address, cell, value });
+            }
+        }
+    }
+}
+
+#[test]
+fn watching() {
+    use crate::machine::Computer;
+    use std::sync::{Arc, Mutex};
+
+    let watcher = Arc::new(Mutex::new(Watcher::new()));
+    watcher.lock().unwrap().watch(9..=9, Access::Write);
+    watcher.lock().unwrap().watch(9..=12, Access::Read);
+    let computer = Computer::from_string("3,9,1008,9,8,9,4,9,99,-1,8").with_tracer(watcher.clone());
+    assert_eq!(computer.standard_run(&[8]), vec![1]);
+    let mut watcher = watcher.lock().unwrap();
+    assert_eq!(watcher.take_hits(), vec![Hit{ address: 0, cell: 9, access: Access::Write, value: 8 },
+                                         Hit{ address: 2, cell: 9, access: Access::Read, value: 8 },
+                                         Hit{ address: 2, cell: 9, access: Access::Write, value: 1 },
+                                         Hit{ address: 6, cell: 9, access: Access::Read, value: 1 }]);
+    assert_eq!(watcher.take_hits(), vec![]);
+    assert_eq!(watcher.modifications(), &[]);
+    assert!(watcher.is_code(7));
+    assert!(!watcher.is_code(9));
+    watcher.unwatch(11);
+    assert_eq!(watcher.watchpoints().len(), 1);
+    assert_eq!(watcher.watchpoints()[0].to_string(), "writes to 9..9");
+
+    // relative reads are found through the base as it was at the time
+    let watcher = Arc::new(Mutex::new(Watcher::new()));
+    watcher.lock().unwrap().watch(0..=100, Access::Read);
+    let computer = Computer::from_string("109,7,209,-6,204,-14,99").with_tracer(watcher.clone());
+    assert_eq!(computer.standard_run(&[]), vec![109]);
+    let hits: Vec<String> = watcher.lock().unwrap().take_hits().iter().map(|x| x.to_string()).collect();
+    assert_eq!(hits, vec!["00000002 read 7 from 1", "00000004 read 109 from 0"]);
+
+    // a program that rewrites an instruction it has already run
+    let watcher = Arc::new(Mutex::new(Watcher::new()));
+    let computer = Computer::from_string("1101,1,1,1,1106,0,8,0,1,0,0,0,99").with_tracer(watcher.clone());
+    computer.standard_run(&[]);
+    let watcher = watcher.lock().unwrap();
+    assert_eq!(watcher.modifications(), &[Modification{ address: 0, cell: 1, value: 2 },
+                                          Modification{ address: 8, cell: 0, value: 2202 }]);
+    assert_eq!(watcher.modifications()[1].to_string(), "00000008 rewrote code at 0 to 2202");
+
+    // the day 5 example only rewrites a cell before it ever runs
+    let watcher = Arc::new(Mutex::new(Watcher::new()));
+    Computer::from_string("1002,4,3,4,33").with_tracer(watcher.clone()).standard_run(&[]);
+    assert_eq!(watcher.lock().unwrap().modifications(), &[]);
+
+    // a dialect instruction's operands are code, even if the standard set has no such opcode
+    use crate::isa::InstructionSet;
+    let watcher = Arc::new(Mutex::new(Watcher::new()));
+    Computer::from_string("104,3,10,0,99").with_instruction_set(Arc::new(InstructionSet::debugging()))
+                                          .with_tracer(watcher.clone())
+                                          .standard_run(&[]);
+    assert!(watcher.lock().unwrap().is_code(3));
+}